@@ -1,6 +1,5 @@
 // RUN: cargo bench --features "unstable"
 
-#![feature(test)]
 #![cfg_attr(feature = "unstable", feature(test))]
 #[cfg(all(feature = "unstable", test))]
 mod bench {
@@ -25,12 +24,12 @@ mod bench {
 
     #[derive(Debug)]
     struct TestResult {
-        value: Result<Option<DataType>, &'static str>,
+        value: Result<Option<DataType>, SchemeError>,
         env: Rc<RefCell<Env>>
     }
 
     fn default_env() -> Rc<RefCell<Env>> {
-        let local = RefCell::new(setup());
+        let local = Box::new(RefCell::new(setup()));
         let env = Env {
             local,
             parent: None
@@ -41,7 +40,7 @@ mod bench {
     }
 
     fn run_with_env(s: &str, env_ref: Rc<RefCell<Env>>) -> TestResult {
-        env_logger::init();
+        let _ = env_logger::init();
         let result = parse(s)
             .and_then(|ast| eval(Some(ast.result), env_ref.clone()));
 
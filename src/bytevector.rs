@@ -0,0 +1,20 @@
+use std::fmt;
+
+use super::Shared;
+
+/// A mutable vector of bytes.
+pub type Bytevector = Shared<Vec<u8>>;
+
+impl PartialEq for Bytevector {
+    fn eq(&self, other: &Bytevector) -> bool {
+        *self.0.borrow() == *other.0.borrow()
+    }
+}
+
+/// Prints the bytes in `#u8(...)` syntax.
+impl fmt::Debug for Bytevector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = self.0.borrow().iter().map(|b| b.to_string()).collect::<Vec<_>>();
+        write!(f, "#u8({})", bytes.join(" "))
+    }
+}
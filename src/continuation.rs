@@ -0,0 +1,179 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+
+use super::{call, DataType, Env, Frame, SchemeError};
+
+/// A `dynamic-wind` whose thunk is running. Winders form a list from the
+/// innermost one outwards, shared between continuations that captured them.
+pub struct Winder {
+    pub before: DataType,
+    pub after: DataType,
+    pub env: Rc<RefCell<Env>>,
+    pub parent: Winders,
+    depth: usize
+}
+
+pub type Winders = Option<Rc<Winder>>;
+
+impl Winder {
+    pub fn new(before: DataType, after: DataType, env: Rc<RefCell<Env>>, parent: Winders) -> Winder {
+        let depth = parent.as_ref().map_or(0, |p| p.depth) + 1;
+        Winder { before, after, env, parent, depth }
+    }
+}
+
+thread_local! {
+    static WINDERS: RefCell<Winders> = const { RefCell::new(None) };
+    /// Ids of the evaluator loops currently running, outermost first.
+    static RUNS: RefCell<Vec<usize>> = const { RefCell::new(vec![]) };
+    static NEXT_RUN: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn winders() -> Winders {
+    WINDERS.with(|w| w.borrow().clone())
+}
+
+pub fn set_winders(winders: Winders) {
+    WINDERS.with(|w| *w.borrow_mut() = winders);
+}
+
+fn depth(winders: &Winders) -> usize {
+    winders.as_ref().map_or(0, |w| w.depth)
+}
+
+fn parent(winders: &Winders) -> Winders {
+    winders.as_ref().and_then(|w| w.parent.clone())
+}
+
+fn same(a: &Winders, b: &Winders) -> bool {
+    match (a.as_ref(), b.as_ref()) {
+        (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false
+    }
+}
+
+/// Moves the dynamic extent from the current winders to `to`, running the
+/// `after` thunks of the `dynamic-wind`s being left, innermost first, and then
+/// the `before` thunks of the ones being entered, outermost first.
+pub fn rewind(to: &Winders) -> Result<(), SchemeError> {
+    let mut common = winders();
+    let mut target = to.clone();
+    while depth(&common) > depth(&target) {
+        common = parent(&common);
+    }
+    while depth(&target) > depth(&common) {
+        target = parent(&target);
+    }
+    while !same(&common, &target) {
+        common = parent(&common);
+        target = parent(&target);
+    }
+
+    while !same(&winders(), &common) {
+        let winder = winders().unwrap();
+        set_winders(winder.parent.clone());
+        call(&winder.after, vec![], winder.env.clone())?;
+    }
+
+    let mut entering = vec![];
+    let mut current = to.clone();
+    while !same(&current, &common) {
+        let winder = current.unwrap();
+        current = winder.parent.clone();
+        entering.push(winder);
+    }
+    for winder in entering.into_iter().rev() {
+        call(&winder.before, vec![], winder.env.clone())?;
+        set_winders(Some(winder));
+    }
+    Ok(())
+}
+
+/// Registers an evaluator loop as running for as long as it is alive.
+pub struct Run {
+    pub id: usize
+}
+
+impl Run {
+    pub fn enter() -> Run {
+        let id = NEXT_RUN.with(|n| {
+            let id = n.get();
+            n.set(id + 1);
+            id
+        });
+        RUNS.with(|runs| runs.borrow_mut().push(id));
+        Run { id }
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        RUNS.with(|runs| runs.borrow_mut().retain(|&id| id != self.id));
+    }
+}
+
+/// The rest of a computation, as captured by `call/cc`.
+///
+/// Code the evaluator runs on behalf of native code, such as a procedural macro
+/// transformer or a `dynamic-wind` thunk run while unwinding, goes through a
+/// nested evaluator loop, and a continuation captured there only holds the
+/// frames of that loop. It can be resumed while the loop is still running,
+/// but not once the native code that started it has returned. A
+/// continuation captured by the outermost loop stands for "return to the top
+/// level" and can be resumed at any time.
+#[derive(Clone)]
+pub struct Continuation(Rc<ContinuationData>);
+
+struct ContinuationData {
+    frames: Vec<Frame>,
+    winders: Winders,
+    /// The loop the continuation was captured in, or `None` for the top level.
+    run: Option<usize>
+}
+
+impl Continuation {
+    pub(crate) fn capture(frames: Vec<Frame>) -> Continuation {
+        let run = RUNS.with(|runs| {
+            let runs = runs.borrow();
+            match runs.len() {
+                0 | 1 => None,
+                _ => runs.last().cloned()
+            }
+        });
+        Continuation(Rc::new(ContinuationData { frames, winders: winders(), run }))
+    }
+
+    pub(crate) fn frames(&self) -> Vec<Frame> {
+        self.0.frames.clone()
+    }
+
+    pub fn winders(&self) -> &Winders {
+        &self.0.winders
+    }
+
+    /// The id of the running loop that can resume the continuation, if any.
+    pub fn target(&self) -> Option<usize> {
+        RUNS.with(|runs| {
+            let runs = runs.borrow();
+            match self.0.run {
+                None => runs.first().cloned(),
+                Some(id) => runs.iter().find(|&&run| run == id).cloned()
+            }
+        })
+    }
+}
+
+impl fmt::Debug for Continuation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let raw = &*self.0 as *const _;
+        f.debug_tuple("Continuation").field(&raw).finish()
+    }
+}
+
+impl PartialEq for Continuation {
+    fn eq(&self, other: &Continuation) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fmt;
+
+use super::{datatype2str, Continuation, DataType, Span};
+
+/// The number of arguments a procedure accepts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Between(usize, usize)
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exactly(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+            Arity::Between(min, max) => count >= min && count <= max
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Arity::Exactly(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+            Arity::Between(min, max) => write!(f, "between {} and {}", min, max)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemeError {
+    /// A variable was referenced before being defined.
+    UnboundVariable(String),
+    /// A procedure was called with the wrong number of arguments.
+    ArityMismatch {
+        procedure: String,
+        expected: Arity,
+        actual: usize
+    },
+    /// A procedure received an argument of the wrong type.
+    WrongType {
+        procedure: String,
+        expected: &'static str,
+        found: Box<DataType>
+    },
+    /// The reader could not make sense of the program text.
+    Syntax {
+        message: String,
+        line: usize,
+        column: usize
+    },
+    /// An exact number was divided by exact zero.
+    DivisionByZero(String),
+    /// A special form was used with the wrong shape, e.g. `(if)`.
+    BadSyntax {
+        keyword: String,
+        message: String
+    },
+    /// A continuation was invoked after the native procedure call it was
+    /// captured in had returned.
+    ExpiredContinuation,
+    /// Not an error: a continuation was invoked, and the evaluator loop `run`
+    /// that resumes it is being unwound to.
+    Throw {
+        run: usize,
+        continuation: Continuation,
+        value: Box<Option<DataType>>
+    },
+    /// Another error, tagged with the source region of the expression that raised it.
+    Located {
+        error: Box<SchemeError>,
+        span: Span
+    }
+}
+
+impl SchemeError {
+    pub fn arity(procedure: &str, expected: Arity, actual: usize) -> SchemeError {
+        SchemeError::ArityMismatch {
+            procedure: procedure.to_string(),
+            expected,
+            actual
+        }
+    }
+
+    pub fn wrong_type(procedure: &str, expected: &'static str, found: &DataType) -> SchemeError {
+        SchemeError::WrongType {
+            procedure: procedure.to_string(),
+            expected,
+            found: Box::new(found.clone())
+        }
+    }
+
+    pub fn bad_syntax(keyword: &str, message: &str) -> SchemeError {
+        SchemeError::BadSyntax {
+            keyword: keyword.to_string(),
+            message: message.to_string()
+        }
+    }
+
+    /// Tags the error with `span`, unless it already points somewhere more precise.
+    pub fn at(self, span: Span) -> SchemeError {
+        match self {
+            SchemeError::Located { .. } | SchemeError::Syntax { .. } | SchemeError::Throw { .. } => self,
+            _ if !span.is_known() => self,
+            error => SchemeError::Located {
+                error: Box::new(error),
+                span
+            }
+        }
+    }
+
+    /// The source region the error was raised from, if known.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            SchemeError::Located { span, .. } => Some(span),
+            SchemeError::Syntax { line, column, .. } => Some(Span {
+                line,
+                column,
+                ..Span::default()
+            }),
+            _ => None
+        }
+    }
+
+    /// The error with any location information stripped off.
+    pub fn unlocated(self) -> SchemeError {
+        match self {
+            SchemeError::Located { error, .. } => error.unlocated(),
+            error => error
+        }
+    }
+}
+
+impl fmt::Display for SchemeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SchemeError::UnboundVariable(ref name) => write!(f, "unbound variable: {}", name),
+            SchemeError::ArityMismatch { ref procedure, ref expected, actual } => {
+                write!(f, "{}: expected {} argument(s), got {}", procedure, expected, actual)
+            }
+            SchemeError::WrongType { ref procedure, expected, ref found } => {
+                write!(f, "{}: expected {}, found {}", procedure, expected, datatype2str(found))
+            }
+            SchemeError::DivisionByZero(ref procedure) => write!(f, "{}: division by zero", procedure),
+            SchemeError::Syntax { ref message, line, column } => {
+                write!(f, "{}:{}: syntax error: {}", line, column, message)
+            }
+            SchemeError::BadSyntax { ref keyword, ref message } => {
+                write!(f, "bad syntax in {}: {}", keyword, message)
+            }
+            SchemeError::ExpiredContinuation => {
+                write!(f, "continuation can no longer be resumed: the procedure call it was captured in has returned")
+            }
+            SchemeError::Throw { .. } => write!(f, "continuation invoked outside of the evaluator"),
+            SchemeError::Located { ref error, span } => write!(f, "{}: {}", span, error)
+        }
+    }
+}
+
+impl Error for SchemeError {}
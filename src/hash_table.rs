@@ -0,0 +1,218 @@
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::{Rc, Weak};
+
+use super::{eqv, DataType, Number, Pair, SchemeError, Shared};
+
+/// How a hash table compares its keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Equivalence {
+    /// `eq?`, which is the same as `eqv?` here.
+    Eq,
+    Eqv,
+    Equal,
+    /// `string=?`, for tables whose keys are all strings.
+    String
+}
+
+impl Equivalence {
+    fn same(&self, a: &DataType, b: &DataType) -> bool {
+        match *self {
+            Equivalence::Eq | Equivalence::Eqv => eqv(a, b),
+            Equivalence::Equal | Equivalence::String => a == b
+        }
+    }
+}
+
+/// A key that does not keep the object it refers to alive. Only pairs,
+/// strings, vectors and bytevectors are held weakly; any other key, such as
+/// a number or a symbol, is a plain value and is held as it is.
+enum WeakKey {
+    Pair(Weak<RefCell<(DataType, DataType)>>),
+    String(Weak<RefCell<String>>),
+    Vector(Weak<RefCell<Vec<DataType>>>),
+    Bytevector(Weak<RefCell<Vec<u8>>>)
+}
+
+enum Key {
+    Strong(DataType),
+    Weak(WeakKey)
+}
+
+impl Key {
+    fn new(key: DataType, weak: bool) -> Key {
+        if !weak {
+            return Key::Strong(key);
+        }
+        match key {
+            DataType::Pair(ref p) => Key::Weak(WeakKey::Pair(Rc::downgrade(&p.0))),
+            DataType::String(ref s) => Key::Weak(WeakKey::String(Rc::downgrade(&s.0))),
+            DataType::Vector(ref v) => Key::Weak(WeakKey::Vector(Rc::downgrade(&v.0))),
+            DataType::Bytevector(ref b) => Key::Weak(WeakKey::Bytevector(Rc::downgrade(&b.0))),
+            key => Key::Strong(key)
+        }
+    }
+
+    /// The key, or `None` once nothing else refers to it.
+    fn get(&self) -> Option<DataType> {
+        match *self {
+            Key::Strong(ref key) => Some(key.clone()),
+            Key::Weak(WeakKey::Pair(ref w)) => w.upgrade().map(|rc| DataType::Pair(Pair(rc))),
+            Key::Weak(WeakKey::String(ref w)) => w.upgrade().map(|rc| DataType::String(Shared(rc))),
+            Key::Weak(WeakKey::Vector(ref w)) => w.upgrade().map(|rc| DataType::Vector(Shared(rc))),
+            Key::Weak(WeakKey::Bytevector(ref w)) => w.upgrade().map(|rc| DataType::Bytevector(Shared(rc)))
+        }
+    }
+}
+
+struct Entry {
+    key: Key,
+    value: DataType
+}
+
+struct Table {
+    equivalence: Equivalence,
+    weak: bool,
+    /// Entries by the hash of their key.
+    buckets: HashMap<u64, Vec<Entry>>
+}
+
+/// A mutable hash table, shared by its clones.
+#[derive(Clone)]
+pub struct HashTable(Rc<RefCell<Table>>);
+
+impl HashTable {
+    /// A table comparing keys with `equivalence`. If `weak` is set, the
+    /// table does not keep its keys alive, and entries disappear once nothing
+    /// else refers to their key.
+    pub fn new(equivalence: Equivalence, weak: bool) -> HashTable {
+        HashTable(Rc::new(RefCell::new(Table { equivalence, weak, buckets: HashMap::new() })))
+    }
+
+    /// The hash of `key`, or an error naming `procedure` if the table can not
+    /// hold it.
+    fn hash(&self, procedure: &str, key: &DataType) -> Result<u64, SchemeError> {
+        let equivalence = self.0.borrow().equivalence;
+        if equivalence == Equivalence::String && !matches!(*key, DataType::String(_)) {
+            return Err(SchemeError::wrong_type(procedure, "string", key));
+        }
+        let mut hasher = DefaultHasher::new();
+        let identity = matches!(equivalence, Equivalence::Eq | Equivalence::Eqv);
+        hash(key, identity, &mut hasher, &mut 64);
+        Ok(hasher.finish())
+    }
+
+    pub fn get(&self, procedure: &str, key: &DataType) -> Result<Option<DataType>, SchemeError> {
+        let hash = self.hash(procedure, key)?;
+        let table = self.0.borrow();
+        let found = table.buckets.get(&hash).and_then(|bucket| bucket.iter().find(|entry|
+            entry.key.get().is_some_and(|k| table.equivalence.same(&k, key))
+        ));
+        Ok(found.map(|entry| entry.value.clone()))
+    }
+
+    pub fn set(&self, procedure: &str, key: DataType, value: DataType) -> Result<(), SchemeError> {
+        let hash = self.hash(procedure, &key)?;
+        let mut table = self.0.borrow_mut();
+        let (equivalence, weak) = (table.equivalence, table.weak);
+        let bucket = table.buckets.entry(hash).or_default();
+        bucket.retain(|entry| entry.key.get().is_some());
+        match bucket.iter_mut().find(|entry| entry.key.get().is_some_and(|k| equivalence.same(&k, &key))) {
+            Some(entry) => entry.value = value,
+            None => bucket.push(Entry { key: Key::new(key, weak), value })
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self, procedure: &str, key: &DataType) -> Result<(), SchemeError> {
+        let hash = self.hash(procedure, key)?;
+        let mut table = self.0.borrow_mut();
+        let equivalence = table.equivalence;
+        if let Some(bucket) = table.buckets.get_mut(&hash) {
+            bucket.retain(|entry| entry.key.get().is_some_and(|k| !equivalence.same(&k, key)));
+        }
+        Ok(())
+    }
+
+    /// The keys and values of the entries, in no particular order.
+    pub fn entries(&self) -> Vec<(DataType, DataType)> {
+        self.purge();
+        self.0.borrow().buckets.values()
+            .flat_map(|bucket| bucket.iter().filter_map(|entry| entry.key.get().map(|k| (k, entry.value.clone()))))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.purge();
+        self.0.borrow().buckets.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the entries of a weak table whose keys are gone.
+    fn purge(&self) {
+        let mut table = self.0.borrow_mut();
+        if table.weak {
+            for bucket in table.buckets.values_mut() {
+                bucket.retain(|entry| entry.key.get().is_some());
+            }
+            table.buckets.retain(|_, bucket| !bucket.is_empty());
+        }
+    }
+}
+
+/// Feeds `value` to `hasher` consistently with `eqv?` if `identity` is set,
+/// or else with `equal?`. At most `budget` pairs and elements are looked at,
+/// so circular structures can be hashed too.
+fn hash<H: Hasher>(value: &DataType, identity: bool, hasher: &mut H, budget: &mut usize) {
+    if *budget == 0 {
+        return;
+    }
+    *budget -= 1;
+    mem::discriminant(value).hash(hasher);
+    match *value {
+        DataType::Bool(b) => b.hash(hasher),
+        DataType::Char(c) => c.hash(hasher),
+        DataType::Symbol(ref s) => s.hash(hasher),
+        // -0.0 is equal? to 0.0, so both have to hash alike, though they are not eqv?
+        DataType::Number(Number::Real(0.0)) if !identity => "0.0".hash(hasher),
+        DataType::Number(ref n) => n.to_string().hash(hasher),
+        DataType::Pair(ref p) if identity => Rc::as_ptr(&p.0).hash(hasher),
+        DataType::String(ref s) if identity => Rc::as_ptr(&s.0).hash(hasher),
+        DataType::Vector(ref v) if identity => Rc::as_ptr(&v.0).hash(hasher),
+        DataType::Bytevector(ref b) if identity => Rc::as_ptr(&b.0).hash(hasher),
+        DataType::Pair(ref p) => {
+            hash(&p.car(), identity, hasher, budget);
+            hash(&p.cdr(), identity, hasher, budget);
+        }
+        DataType::String(ref s) => s.borrow().hash(hasher),
+        DataType::Vector(ref v) => {
+            for item in v.borrow().iter() {
+                hash(item, identity, hasher, budget);
+            }
+        }
+        DataType::Bytevector(ref b) => b.borrow().hash(hasher),
+        // procedures and the like only hash by their kind
+        _ => {}
+    }
+}
+
+/// Tables are only equal to themselves.
+impl PartialEq for HashTable {
+    fn eq(&self, other: &HashTable) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for HashTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let table = self.0.borrow();
+        write!(f, "#<{}hash-table {:?}>", if table.weak { "weak " } else { "" }, table.equivalence)
+    }
+}
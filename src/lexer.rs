@@ -0,0 +1,318 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use super::SchemeError;
+
+/// A region of the source program. `line` and `column` are 1-based and point at
+/// the first character, `start`/`end` are byte offsets into the source.
+/// Nodes created by the interpreter itself carry the default (all zero) span.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            line: self.line,
+            column: self.column,
+            start: self.start,
+            end: other.end
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum TokenKind {
+    Open,
+    /// `#(`, which opens a vector literal.
+    OpenVector,
+    /// `#u8(`, which opens a bytevector literal.
+    OpenBytevector,
+    Close,
+    /// A string literal, with its escapes already decoded.
+    String(String),
+    /// `#;`, which comments out the datum that follows it.
+    DatumComment,
+    /// One of `'`, `` ` ``, `,` and `,@`, holding the name of the form it abbreviates.
+    Abbreviation(&'static str),
+    Atom
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// The source text of the token, exactly as written.
+    pub text: String,
+    pub span: Span
+}
+
+pub struct Lexer<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    source: &'a str,
+    line: usize,
+    column: usize
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Lexer<'a> {
+        Lexer {
+            chars: source.char_indices().peekable(),
+            source,
+            line: 1,
+            column: 1
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn peek_second(&mut self) -> Option<char> {
+        let mut ahead = self.chars.clone();
+        ahead.next();
+        ahead.next().map(|(_, c)| c)
+    }
+
+    fn offset(&mut self) -> usize {
+        match self.chars.peek() {
+            Some(&(i, _)) => i,
+            None => self.source.len()
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let (_, c) = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn start_span(&mut self) -> Span {
+        let start = self.offset();
+        Span {
+            line: self.line,
+            column: self.column,
+            start,
+            end: start
+        }
+    }
+
+    fn finish(&mut self, kind: TokenKind, mut span: Span) -> Token {
+        span.end = self.offset();
+        Token {
+            kind,
+            text: self.source[span.start..span.end].to_string(),
+            span
+        }
+    }
+
+    fn error(&self, message: &str, span: Span) -> SchemeError {
+        SchemeError::Syntax {
+            message: message.to_string(),
+            line: span.line,
+            column: span.column
+        }
+    }
+
+    /// Skips whitespace, `; line comments` and `#| block comments |#`.
+    fn skip_atmosphere(&mut self) -> Result<(), SchemeError> {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == ';' {
+                while let Some(c) = self.bump() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else if c == '#' && self.peek_second() == Some('|') {
+                self.block_comment()?;
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips a block comment, which may contain nested block comments.
+    fn block_comment(&mut self) -> Result<(), SchemeError> {
+        let span = self.start_span();
+        let mut depth = 0;
+        loop {
+            match (self.bump(), self.peek()) {
+                (Some('#'), Some('|')) => {
+                    self.bump();
+                    depth += 1;
+                }
+                (Some('|'), Some('#')) => {
+                    self.bump();
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                (Some(_), _) => {}
+                (None, _) => return Err(self.error("unterminated block comment", span))
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Option<Result<Token, SchemeError>> {
+        if let Err(e) = self.skip_atmosphere() {
+            return Some(Err(e));
+        }
+
+        let span = self.start_span();
+        let kind = match self.bump()? {
+            '(' => TokenKind::Open,
+            ')' => TokenKind::Close,
+            '\'' => TokenKind::Abbreviation("quote"),
+            '`' => TokenKind::Abbreviation("quasiquote"),
+            ',' if self.peek() == Some('@') => {
+                self.bump();
+                TokenKind::Abbreviation("unquote-splicing")
+            }
+            ',' => TokenKind::Abbreviation("unquote"),
+            '#' if self.source[self.offset()..].starts_with("u8(") => {
+                self.bump();
+                self.bump();
+                self.bump();
+                TokenKind::OpenBytevector
+            }
+            '#' if self.peek() == Some('(') => {
+                self.bump();
+                TokenKind::OpenVector
+            }
+            '#' if self.peek() == Some(';') => {
+                self.bump();
+                TokenKind::DatumComment
+            }
+            '"' => match self.string_literal(span) {
+                Ok(value) => TokenKind::String(value),
+                Err(e) => return Some(Err(e))
+            },
+            first => {
+                // the character after `#\` is part of the literal even if it is a delimiter, as in `#\(`
+                if first == '#' && self.peek() == Some('\\') {
+                    self.bump();
+                    self.bump();
+                }
+                while let Some(c) = self.peek() {
+                    if is_delimiter(c) {
+                        break;
+                    }
+                    self.bump();
+                }
+                TokenKind::Atom
+            }
+        };
+        Some(Ok(self.finish(kind, span)))
+    }
+
+    /// Reads the rest of a string literal whose opening quote has been consumed.
+    fn string_literal(&mut self, span: Span) -> Result<String, SchemeError> {
+        let mut value = String::new();
+        loop {
+            let escape = self.start_span();
+            match self.bump() {
+                Some('"') => return Ok(value),
+                Some('\\') => {
+                    match self.bump() {
+                        Some('a') => value.push('\u{7}'),
+                        Some('b') => value.push('\u{8}'),
+                        Some('t') => value.push('\t'),
+                        Some('n') => value.push('\n'),
+                        Some('r') => value.push('\r'),
+                        Some('"') => value.push('"'),
+                        Some('\\') => value.push('\\'),
+                        Some('|') => value.push('|'),
+                        Some('x') | Some('X') => value.push(self.hex_escape(escape)?),
+                        Some(c) if c == '\n' || is_intraline_whitespace(c) => self.line_continuation(c, escape)?,
+                        Some(_) => return Err(self.error("unknown escape sequence in string", escape)),
+                        None => return Err(self.error("can not find an end quote", span))
+                    }
+                }
+                Some(c) => value.push(c),
+                None => return Err(self.error("can not find an end quote", span))
+            }
+        }
+    }
+
+    /// Reads the `HH;` part of a `\xHH;` escape.
+    fn hex_escape(&mut self, escape: Span) -> Result<char, SchemeError> {
+        let mut digits = String::new();
+        loop {
+            match self.bump() {
+                Some(';') => break,
+                Some(c) if c.is_ascii_hexdigit() => digits.push(c),
+                _ => return Err(self.error("malformed \\x escape in string", escape))
+            }
+        }
+        u32::from_str_radix(&digits, 16).ok()
+            .and_then(::std::char::from_u32)
+            .ok_or_else(|| self.error("invalid character in \\x escape", escape))
+    }
+
+    /// Skips `\<intraline whitespace>*<newline><intraline whitespace>*`, the first
+    /// character after the backslash being `first`.
+    fn line_continuation(&mut self, first: char, escape: Span) -> Result<(), SchemeError> {
+        let mut seen_newline = first == '\n';
+        while let Some(c) = self.peek() {
+            if c == '\n' && !seen_newline {
+                seen_newline = true;
+            } else if !is_intraline_whitespace(c) {
+                break;
+            }
+            self.bump();
+        }
+        if seen_newline {
+            Ok(())
+        } else {
+            Err(self.error("unknown escape sequence in string", escape))
+        }
+    }
+}
+
+fn is_intraline_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || c == '(' || c == ')' || c == '"' || c == ';'
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token, SchemeError>;
+
+    fn next(&mut self) -> Option<Result<Token, SchemeError>> {
+        self.next_token()
+    }
+}
+
+pub fn tokenize(program: &str) -> Result<Vec<Token>, SchemeError> {
+    Lexer::new(program).collect()
+}
@@ -1,35 +1,58 @@
 #[macro_use]
 extern crate log;
 extern crate env_logger;
+extern crate num_bigint;
+extern crate num_complex;
+extern crate num_rational;
+extern crate num_traits;
 
 //use std;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::fmt;
-use std::f64;
+use std::cmp::Ordering;
+
+mod bytevector;
+mod continuation;
+mod error;
+mod hash_table;
+mod lexer;
+mod macros;
+mod number;
+mod pair;
+mod shared;
+mod string;
+mod vector;
+
+use continuation::{Run, Winder};
+pub use bytevector::Bytevector;
+pub use continuation::Continuation;
+pub use error::{Arity, SchemeError};
+pub use hash_table::{Equivalence, HashTable};
+pub use lexer::{Span, Token, TokenKind};
+pub use macros::{Macro, SyntaxRules};
+pub use number::Number;
+pub use pair::Pair;
+pub use shared::Shared;
+pub use string::SchemeString;
+pub use vector::Vector;
 
 #[macro_export]
 macro_rules! tuplet {
     { ($y:ident $(, $x:ident)*) = $v:expr } => {
-        let ($y,$($x),*, _) = tuplet!($v ; 1 ; ($($x),*) ; ($v.get(0)) ); };
+        let ($y,$($x),*, _) = tuplet!($v ; 1 ; ($($x),*) ; ($v.first()) ); };
     { ($y:ident , * $x:ident) = $v:expr } => {
-        let ($y,$x) = tuplet!($v ; 1 ; () ; ($v.get(0)) ); };
+        let ($y,$x) = tuplet!($v ; 1 ; () ; ($v.first()) ); };
     { ($y:ident $(, $x:ident)* , * $z:ident) = $v:expr } => {
-        let ($y,$($x),*, $z) = tuplet!($v ; 1 ; ($($x),*) ; ($v.get(0)) ); };
+        let ($y,$($x),*, $z) = tuplet!($v ; 1 ; ($($x),*) ; ($v.first()) ); };
     { $v:expr ; $j:expr ; ($y:ident $(, $x:ident)*) ; ($($a:expr),*)  } => {
         tuplet!( $v ; $j+1 ; ($($x),*) ; ($($a),*,$v.get($j)) ) };
     { $v:expr ; $j:expr ; () ; ($($a:expr),*) } => {
         {
-            if $v.len() >= $j {
-                let remain = $v.len() - $j;
-                if remain > 0 {
-                    ($($a),*, Some(&$v[$j..]))
-                } else {
-                    ($($a),*, None)
-                }
-            } else {
-                ($($a),*, None)
+            match $v.len().checked_sub($j) {
+                Some(remain) if remain > 0 => ($($a),*, Some(&$v[$j..])),
+                _ => ($($a),*, None)
             }
         }
     }
@@ -37,24 +60,30 @@ macro_rules! tuplet {
 
 #[macro_export]
 macro_rules! define_comparison {
-    ($proc:ident, $name:pat, $func:expr) => {
+    ($proc:ident, $name:expr, $func:expr) => {
+        define_comparison!($proc, $name, reals, $func);
+    };
+    ($proc:ident, $name:expr, $check:ident, $func:expr) => {
         let $proc = DataType::Proc(Function( Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
-                debug!("Function - name: {:?} - Args: {:?}", stringify!($name), vec);
-                if vec.len() != 2 {
-                    return Err("function requires 2 arguments only");
-                }
-                tuplet!((a,b) = vec);
-
-                if let (Some(&DataType::Number(ref a0)), Some(&DataType::Number(ref b0))) = (a, b) {
-                    let a1: f64 = a0.clone().into();
-                    let b1: f64 = b0.clone().into();
-                    let desc = format!("{} {} {}", a1, stringify!($name), b1);
-                    debug!("Description: {}", desc);
-                    Ok(Some(DataType::Bool($func(a1, b1))))
-                } else {
-                    return Err("wrong argument datatype");
-                }
+                debug!("Function - name: {:?} - Args: {:?}", $name, vec);
+                check_arity($name, &vec, Arity::Exactly(2))?;
+                let numbers = $check($name, &vec)?;
+                let (a1, b1) = (&numbers[0], &numbers[1]);
+                let desc = format!("{} {} {}", a1, $name, b1);
+                debug!("Description: {}", desc);
+                Ok(Some(DataType::Bool(a1.compare(b1).map_or(false, $func))))
+            })));
+    };
+}
 
+#[macro_export]
+macro_rules! define_numeric {
+    ($proc:ident, $name:expr, $func:expr) => {
+        let $proc = DataType::Proc(Function( Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+                debug!("Function - name: {:?} - Args: {:?}", $name, vec);
+                check_arity($name, &vec, Arity::Exactly(1))?;
+                let numbers = numbers($name, &vec)?;
+                Ok(Some(DataType::Number($func(&numbers[0]))))
             })));
     };
 }
@@ -63,27 +92,60 @@ macro_rules! define_comparison {
 #[derive(Debug)]
 #[derive(PartialEq)]
 pub enum AST {
-    Integer(i64),
-    Float(f64),
-    Symbol(String),
-    Children(Vec<AST>)
+    Number(Number, Span),
+    Symbol(String, Span),
+    String(String, Span),
+    Children(Vec<AST>, Span),
+    /// A `#(...)` vector literal.
+    Vector(Vec<AST>, Span),
+    /// A `#u8(...)` bytevector literal.
+    Bytevector(Vec<u8>, Span)
+}
+
+impl AST {
+    pub fn span(&self) -> Span {
+        match *self {
+            AST::Number(_, span) | AST::Symbol(_, span) | AST::String(_, span) |
+            AST::Children(_, span) | AST::Vector(_, span) | AST::Bytevector(_, span) => span
+        }
+    }
 }
 
 #[derive(Debug)]
 #[derive(PartialEq)]
 pub struct ReadFromTokenResult {
-    pub remain: Vec<String>,
+    pub remain: Vec<Token>,
     pub result: AST
 }
 
 #[derive(Clone)]
-#[derive(PartialEq)]
 pub struct Procedure {
     body: AST,
     params: Vec<DataType>,
+    /// The parameter that receives the arguments after `params` as a list, if any.
+    rest: Option<String>,
     env: Rc<RefCell<Env>>
 }
 
+impl Procedure {
+    pub fn arity(&self) -> Arity {
+        match self.rest {
+            Some(_) => Arity::AtLeast(self.params.len()),
+            None => Arity::Exactly(self.params.len())
+        }
+    }
+}
+
+/// Procedures are only equal if they were made by the same `lambda` in the same
+/// environment. The environment is compared by identity: it usually holds the
+/// procedure itself, so comparing its contents would never end.
+impl PartialEq for Procedure {
+    fn eq(&self, other: &Procedure) -> bool {
+        Rc::ptr_eq(&self.env, &other.env) && self.params == other.params &&
+            self.rest == other.rest && self.body == other.body
+    }
+}
+
 impl fmt::Debug for Procedure {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let env_raw = &self.env as *const _;
@@ -91,15 +153,20 @@ impl fmt::Debug for Procedure {
         f.debug_struct("Procedure")
             .field("body", &self.body)
             .field("params", &self.params)
+            .field("rest", &self.rest)
             .field("env", &env_raw)
             .finish()
     }
 }
 
-pub struct Function(pub Rc<Fn(Vec<DataType>, Rc<RefCell<Env>>) -> Result<Option<DataType>, &'static str>>);
+pub type FunctionResult = Result<Option<DataType>, SchemeError>;
+
+type NativeFn = dyn Fn(Vec<DataType>, Rc<RefCell<Env>>) -> FunctionResult;
+
+pub struct Function(pub Rc<NativeFn>);
 
 impl Function {
-    fn call(&self, arguments: Vec<DataType>, env: Rc<RefCell<Env>>) -> Result<Option<DataType>, &'static str> {
+    fn call(&self, arguments: Vec<DataType>, env: Rc<RefCell<Env>>) -> FunctionResult {
         (self.0)(arguments, env)
     }
 }
@@ -119,38 +186,111 @@ impl fmt::Debug for Function {
 
 impl std::cmp::PartialEq for Function {
     fn eq(&self, other: &Function) -> bool {
-        let self_raw = &self.0 as *const _;
-        let other_raw = &other.0 as *const _;
-        self_raw == other_raw
+        Rc::ptr_eq(&self.0, &other.0)
     }
 }
 
-trait FloatIterExt {
-    fn float_min(&mut self) -> f64;
-    fn float_max(&mut self) -> f64;
-}
-
-impl<T> FloatIterExt for T where T: Iterator<Item=f64> {
-    fn float_max(&mut self) -> f64 {
-        self.fold(f64::NAN, f64::max)
-    }
-
-    fn float_min(&mut self) -> f64 {
-        self.fold(f64::NAN, f64::min)
-    }
+/// Procedures built into the evaluator itself rather than written as a
+/// `Function`, because they need to control what is evaluated next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Primitive {
+    Apply,
+    CallCC,
+    CallWithValues,
+    DynamicWind,
+    Map,
+    ForEach,
+    VectorMap,
+    VectorForEach,
+    HashTableWalk,
+    Member,
+    Assoc,
+    HashTableRef,
+    HashTableUpdate,
+    HashTableUpdateDefault
 }
 
 #[derive(Clone, Debug)]
 #[derive(PartialEq)]
 pub enum DataType {
     Bool(bool),
-    Pair((Box<DataType>, Box<DataType>)),
-    Number(f64),
+    Pair(Pair),
+    /// The empty list.
+    Null,
+    Number(Number),
     Symbol(String),
-    String(String),
+    String(SchemeString),
+    Char(char),
+    Vector(Vector),
+    Bytevector(Bytevector),
+    HashTable(HashTable),
     Proc(Function),
-    List(Vec<DataType>),
-    Lambda(Procedure)
+    Primitive(Primitive),
+    Continuation(Continuation),
+    Macro(Macro),
+    /// What `values` returns when given anything but exactly one value.
+    Values(Vec<DataType>),
+    /// Stands in for the missing value of an expression such as a one-armed
+    /// `if` whose test is false, where a value is needed, e.g. as an argument.
+    Unspecified,
+    Lambda(Procedure),
+    /// A `case-lambda`: calls the first of its procedures that accepts the arguments.
+    CaseLambda(Vec<Procedure>)
+}
+
+impl DataType {
+    /// A new mutable string holding `s`.
+    pub fn string<S: Into<String>>(s: S) -> DataType {
+        DataType::String(SchemeString::new(s.into()))
+    }
+
+    pub fn cons(car: DataType, cdr: DataType) -> DataType {
+        DataType::Pair(Pair::new(car, cdr))
+    }
+
+    /// A proper list of `items`.
+    pub fn list(items: Vec<DataType>) -> DataType {
+        DataType::list_with_tail(items, DataType::Null)
+    }
+
+    /// `items` consed onto `tail`, e.g. the improper list `(1 2 . 3)`.
+    pub fn list_with_tail(items: Vec<DataType>, tail: DataType) -> DataType {
+        items.into_iter().rev().fold(tail, |cdr, car| DataType::cons(car, cdr))
+    }
+
+    /// The elements of a proper list, or `None` if `self` is not one, including
+    /// when its cdrs loop back on themselves.
+    pub fn list_items(&self) -> Option<Vec<DataType>> {
+        let mut items = vec![];
+        let mut current = self.clone();
+        // moves at half the speed of `current`, which catches up with it in a cycle
+        let mut lag = self.clone();
+        loop {
+            current = match current {
+                DataType::Null => return Some(items),
+                DataType::Pair(ref pair) => {
+                    items.push(pair.car());
+                    pair.cdr()
+                }
+                _ => return None
+            };
+            if items.len() % 2 == 0 {
+                if let DataType::Pair(ref pair) = lag.clone() {
+                    lag = pair.cdr();
+                }
+                if let (DataType::Pair(a), DataType::Pair(b)) = (&lag, &current) {
+                    if a.ptr_eq(b) {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn is_procedure(&self) -> bool {
+        matches!(*self, DataType::Proc(_) | DataType::Primitive(_) | DataType::Continuation(_) |
+                 DataType::Lambda(_) | DataType::CaseLambda(_))
+    }
 }
 
 #[derive(Debug)]
@@ -162,16 +302,9 @@ pub struct Env {
 }
 
 impl Env {
-    fn get(&self, key: &String) -> Option<DataType> {
-        match self.local.borrow().get::<str>(key) {
-            Some(&DataType::Bool(b)) => Some(DataType::Bool(b)),
-            Some(&DataType::Pair(ref p)) => Some(DataType::Pair(p.clone())),
-            Some(&DataType::Number(f)) => Some(DataType::Number(f)),
-            Some(&DataType::Symbol(ref ss)) => Some(DataType::Symbol(ss.clone())),
-            Some(&DataType::String(ref ss)) => Some(DataType::String(ss.clone())),
-            Some(&DataType::Proc(ref p)) => Some(DataType::Proc(p.clone())),
-            Some(&DataType::List(ref l)) => Some(DataType::List(l.clone())),
-            Some(&DataType::Lambda(ref p)) => Some(DataType::Lambda(p.clone())),
+    fn get(&self, key: &str) -> Option<DataType> {
+        match self.local.borrow().get(key) {
+            Some(data) => Some(data.clone()),
             None => {
                 match self.parent {
                     Some(ref some_parent) => {
@@ -183,1100 +316,2988 @@ impl Env {
             }
         }
     }
+
+    /// Updates the binding of `key` in the innermost frame that has one.
+    /// Returns false if `key` is unbound.
+    fn set(&self, key: &str, value: DataType) -> bool {
+        if let Some(data) = self.local.borrow_mut().get_mut(key) {
+            *data = value;
+            return true;
+        }
+        match self.parent {
+            Some(ref some_parent) => some_parent.borrow().set(key, value),
+            None => false
+        }
+    }
 }
 
-pub fn parse(program: &str) -> Result<ReadFromTokenResult, &'static str> {
+pub fn parse(program: &str) -> Result<ReadFromTokenResult, SchemeError> {
     debug!("program: {}", program);
-    let wrap_program = format!("(begin {})", program);
 
-    let tokens = tokenize(&wrap_program);
+    let mut tokens = lexer::tokenize(program)?;
     debug!("tokens: {:?}", tokens);
-    let ast = read_from_tokens(tokens.clone());
-    debug!("ast: {:?}", ast);
-    return ast;
-}
 
-fn tokenize(program: &str) -> Vec<String>
-{
-    let iterator = Box::new(program.chars());
-    let count = iterator.clone().count();
-    let vec = iterator.fold(Vec::with_capacity(count), |mut acc, x| {
-        if x == '(' || x == ')' {
-            acc.extend(vec![' ', x, ' '])
-        } else {
-            acc.push(x)
+    // the whole program is evaluated as the body of a `begin`
+    let mut forms = vec![AST::Symbol("begin".to_string(), Span::default())];
+    loop {
+        tokens = skip_datum_comments(tokens)?;
+        if tokens.is_empty() {
+            break;
         }
-        acc
-    });
-    let s: String = vec.into_iter().collect();
-    let ss: Vec<String> = s.split_whitespace().map(|x| x.to_string()).collect();
-    ss
+        let data = read_from_tokens(tokens)?;
+        forms.push(data.result);
+        tokens = data.remain;
+    }
+    let span = match (forms.get(1), forms.last()) {
+        (Some(first), Some(last)) => first.span().to(last.span()),
+        _ => Span::default()
+    };
+    let ast = AST::Children(forms, span);
+    debug!("ast: {:?}", ast);
+    Ok(ReadFromTokenResult {
+        remain: tokens,
+        result: ast
+    })
 }
 
-fn read_from_tokens(mut tokens: Vec<String>) -> Result<ReadFromTokenResult, &'static str> {
-    if tokens.len() > 0 {
-        let token = tokens.remove(0);
-
-        if token == "(" {
-            let mut vec: Vec<AST> = vec![];
-            let mut tmp_tokens = tokens.clone();
+fn syntax_error(message: &str, token: &Token) -> SchemeError {
+    SchemeError::Syntax {
+        message: message.to_string(),
+        line: token.span.line,
+        column: token.span.column
+    }
+}
 
-            if tmp_tokens.is_empty() {
-                return Err("syntax error");
-            }
+/// Drops any leading `#;` comments, along with the datum each of them comments out.
+fn skip_datum_comments(mut tokens: Vec<Token>) -> Result<Vec<Token>, SchemeError> {
+    while matches!(tokens.first(), Some(token) if token.kind == TokenKind::DatumComment) {
+        let comment = tokens.remove(0);
+        if tokens.is_empty() {
+            return Err(syntax_error("expected a datum after #;", &comment));
+        }
+        tokens = read_from_tokens(tokens)?.remain;
+    }
+    Ok(tokens)
+}
 
-            while !tmp_tokens.is_empty() {
-                if tmp_tokens.first().unwrap() == ")" {
-                    break
-                } else {
-                    let start_quote_option = match tmp_tokens.clone().first() {
-                        Some(first_word) => {
-                            if first_word.starts_with('\"') {
-                                debug!("detect a start quote of string");
-                                Some(tmp_tokens.clone())
-                            } else {
-                                None
-                            }
-                        }
-                        None => None
-                    };
-                    if let Some(rest_str) = start_quote_option {
-                        debug!("rest_str: {:?}", rest_str);
-                        match rest_str.iter().position(|string_tag| if string_tag.ends_with('\"') { true } else { false }) {
-                            Some(i) => {
-                                debug!("detect an end quote of string");
-                                let str_result = (rest_str[0..i + 1]).join(" ");
-                                let rest_tokens = (rest_str[i + 1..]).iter().map(|&ref x| x.to_string()).collect::<Vec<String>>();
-                                debug!("str_result: {:?}", str_result);
-                                debug!("rest_tokens: {:?}", rest_tokens);
-                                vec.push(AST::Symbol(str_result));
-                                tmp_tokens = rest_tokens.clone();
-                            }
-                            None => { return Err("can not find an end quote"); }
-                        }
-                    } else {
-                        match read_from_tokens(tmp_tokens.clone()) {
-                            Ok(data) => {
-                                vec.push(data.result);
-                                tmp_tokens = data.remain.clone();
-                            }
-                            Err(e) => { return Err(e); }
-                        }
-                    }
+fn read_from_tokens(tokens: Vec<Token>) -> Result<ReadFromTokenResult, SchemeError> {
+    let mut tokens = skip_datum_comments(tokens)?;
+    if tokens.is_empty() {
+        return Err(SchemeError::Syntax {
+            message: "unexpected EOF while reading".to_string(),
+            line: 1,
+            column: 1
+        });
+    }
+    let token = tokens.remove(0);
+
+    if token.kind == TokenKind::Open || token.kind == TokenKind::OpenVector || token.kind == TokenKind::OpenBytevector {
+        let mut vec: Vec<AST> = vec![];
+
+        loop {
+            tokens = skip_datum_comments(tokens)?;
+            match tokens.first() {
+                Some(next) if next.kind == TokenKind::Close => break,
+                Some(_) => {
+                    let data = read_from_tokens(tokens)?;
+                    vec.push(data.result);
+                    tokens = data.remain;
                 }
+                None => return Err(syntax_error("unexpected EOF while reading", &token))
             }
-            if tmp_tokens.is_empty() {
-                return Err("syntax error");
+        }
+        let close = tokens.remove(0);
+        let span = token.span.to(close.span);
+        if token.kind == TokenKind::OpenVector {
+            if vec.iter().any(is_dot) {
+                return Err(syntax_error("unexpected . in vector", &token));
             }
-            tmp_tokens.remove(0);
-            Ok(
-                ReadFromTokenResult {
-                    remain: tmp_tokens,
-                    result: AST::Children(vec)
-                }
-            )
-        } else if token == ")" {
-            Err("unexpected )")
-        } else {
-            Ok(
-                ReadFromTokenResult {
-                    remain: tokens,
-                    result: atom(&token)
-                }
-            )
+            return Ok(ReadFromTokenResult { remain: tokens, result: AST::Vector(vec, span) });
+        }
+        if token.kind == TokenKind::OpenBytevector {
+            let bytes = vec.iter().map(|ast| match *ast {
+                AST::Number(Number::Integer(n), _) if (0..256).contains(&n) => Ok(n as u8),
+                _ => Err(syntax_error("bytevector elements must be bytes", &token))
+            }).collect::<Result<Vec<u8>, _>>()?;
+            return Ok(ReadFromTokenResult { remain: tokens, result: AST::Bytevector(bytes, span) });
+        }
+        let n = vec.len();
+        if vec.iter().enumerate().any(|(i, ast)| is_dot(ast) && (i == 0 || i + 2 != n)) {
+            return Err(syntax_error("bad dotted list", &token));
         }
+        Ok(
+            ReadFromTokenResult {
+                remain: tokens,
+                result: AST::Children(vec, span)
+            }
+        )
+    } else if token.kind == TokenKind::Close {
+        Err(syntax_error("unexpected )", &token))
+    } else if let TokenKind::Abbreviation(keyword) = token.kind {
+        let tokens = skip_datum_comments(tokens)?;
+        if tokens.is_empty() {
+            return Err(syntax_error(&format!("expected a datum after {}", token.text), &token));
+        }
+        let data = read_from_tokens(tokens)?;
+        let span = token.span.to(data.result.span());
+        Ok(
+            ReadFromTokenResult {
+                remain: data.remain,
+                result: AST::Children(vec![AST::Symbol(keyword.to_string(), token.span), data.result], span)
+            }
+        )
+    } else if let TokenKind::String(ref value) = token.kind {
+        Ok(
+            ReadFromTokenResult {
+                remain: tokens,
+                result: AST::String(value.clone(), token.span)
+            }
+        )
     } else {
-        Err("unexpected EOF while reading")
+        Ok(
+            ReadFromTokenResult {
+                remain: tokens,
+                result: atom(&token)
+            }
+        )
     }
 }
 
-fn atom(token: &str) -> AST {
-    let to_int = token.parse::<i64>();
-    let to_float = token.parse::<f64>();
+fn atom(token: &Token) -> AST {
+    match Number::parse(&token.text) {
+        Some(n) => AST::Number(n, token.span),
+        None => AST::Symbol(token.text.clone(), token.span)
+    }
+}
 
-    if to_int.is_ok() {
-        AST::Integer(to_int.unwrap_or_default())
-    } else if to_float.is_ok() {
-        AST::Float(to_float.unwrap_or_default())
-    } else {
-        AST::Symbol(token.to_string())
+/// The names of the characters that can be written as `#\name`.
+const CHAR_NAMES: [(&str, char); 9] = [
+    ("alarm", '\u{7}'), ("backspace", '\u{8}'), ("delete", '\u{7f}'), ("escape", '\u{1b}'),
+    ("newline", '\n'), ("null", '\0'), ("return", '\r'), ("space", ' '), ("tab", '\t')
+];
+
+/// Reads a literal starting with `#`: a boolean or a character.
+fn parse_hash(s: &str) -> Result<DataType, SchemeError> {
+    match s {
+        "#t" => Ok(DataType::Bool(true)),
+        "#f" => Ok(DataType::Bool(false)),
+        _ if s.starts_with("#\\") => parse_char(&s[2..])
+            .map(DataType::Char)
+            .ok_or_else(|| SchemeError::bad_syntax(s, "unknown character name")),
+        _ => Err(SchemeError::bad_syntax(s, "unknown # syntax"))
     }
 }
 
-pub fn eval(ast_option: Option<AST>, env: Rc<RefCell<Env>>) -> Result<Option<DataType>, &'static str> {
-    debug!("eval");
-    debug!("{:?}", ast_option);
-    match ast_option.clone() {
-        Some(AST::Symbol(s)) => {
-            debug!("ast is a symbol: {:?}", s);
-            if s.starts_with("#") {
-                if s.len() != 2 {
-                    return Err("syntax error");
-                }
-                let c_option = s.chars().nth(1);
-                if let Some('t') = c_option {
-                    Ok(Some(DataType::Bool(true)))
-                } else if let Some('f') = c_option {
-                    Ok(Some(DataType::Bool(false)))
-                } else {
-                    Err("syntax error")
-                }
-            } else if s.len() > 1 && s.starts_with("'") {
-                let slice = &s[1..s.len()];
-                Ok(Some(DataType::Symbol(slice.to_string())))
-            } else if s.starts_with("\"") && s.ends_with("\"") {
-                Ok(Some(DataType::String((&s[1..s.len() - 1]).to_string())))
-            } else {
-                match env.borrow().get(&s) {
-                    Some(data) => Ok(Some(data)),
-                    None => Err("symbol is not defined.")
-                }
-            }
+/// The character written after `#\`: itself, a name such as `space`, or a hex scalar value such as `x3BB`.
+fn parse_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        (Some('x'), Some(_)) => u32::from_str_radix(&name[1..], 16).ok().and_then(::std::char::from_u32),
+        _ => CHAR_NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, c)| c)
+    }
+}
+
+/// Writes `c` back in `#\` syntax.
+fn write_char(c: char) -> String {
+    match CHAR_NAMES.iter().find(|&&(_, named)| named == c) {
+        Some(&(name, _)) => format!("#\\{}", name),
+        None if c.is_control() => format!("#\\x{:x}", c as u32),
+        None => format!("#\\{}", c)
+    }
+}
+
+pub fn eval(ast_option: Option<AST>, env: Rc<RefCell<Env>>) -> FunctionResult {
+    match ast_option {
+        Some(ast) => {
+            let span = ast.span();
+            run(State::Eval(ast, env)).map_err(|e| e.at(span))
         }
-        Some(AST::Children(list)) => {
-            debug!("ast is a children: {:?}", list);
+        None => Ok(None)
+    }
+}
+
+/// Calls `procedure` with `args` from native code, e.g. the function given to `map`.
+fn call(procedure: &DataType, args: Vec<DataType>, env: Rc<RefCell<Env>>) -> FunctionResult {
+    run(State::Apply(procedure.clone(), args, env))
+}
+
+/// What the evaluator does next: evaluate an expression, call a procedure, or
+/// hand a value to the innermost pending `Frame`.
+enum State {
+    Eval(AST, Rc<RefCell<Env>>),
+    Apply(DataType, Vec<DataType>, Rc<RefCell<Env>>),
+    Return(Option<DataType>)
+}
+
+/// The work left to do once the expression currently being evaluated has
+/// produced its value. The evaluator keeps these on an explicit stack instead
+/// of recursing, so a procedure called in tail position replaces its caller's
+/// state rather than growing the Rust stack, and `call/cc` can capture the rest
+/// of the computation by copying the stack.
+#[derive(Clone)]
+enum Frame {
+    /// Waiting for the test of an `if`, `when` or `unless`.
+    If {
+        consequent: Option<AST>,
+        alternative: Option<AST>,
+        env: Rc<RefCell<Env>>
+    },
+    /// Waiting for the test of the `cond` clause `clause`; `rest` holds the
+    /// clauses after it in reverse.
+    Cond {
+        clause: Vec<AST>,
+        rest: Vec<AST>,
+        env: Rc<RefCell<Env>>,
+        span: Span
+    },
+    /// Waiting for the key of a `case`.
+    Case {
+        clauses: Vec<AST>,
+        env: Rc<RefCell<Env>>,
+        span: Span
+    },
+    /// Evaluating the operands of an `and`; `rest` holds the remaining ones in reverse.
+    And {
+        rest: Vec<AST>,
+        env: Rc<RefCell<Env>>
+    },
+    /// Evaluating the operands of an `or`; `rest` holds the remaining ones in reverse.
+    Or {
+        rest: Vec<AST>,
+        env: Rc<RefCell<Env>>
+    },
+    /// Evaluating the forms of a `begin`; `rest` holds the remaining ones in reverse.
+    Sequence {
+        rest: Vec<AST>,
+        env: Rc<RefCell<Env>>
+    },
+    /// Waiting for the value of a `define`.
+    Define {
+        name: String,
+        env: Rc<RefCell<Env>>
+    },
+    /// Waiting for the value of a `set!`.
+    Set {
+        name: String,
+        env: Rc<RefCell<Env>>,
+        span: Span
+    },
+    /// Evaluating the operator and operands of a procedure call, left to right.
+    /// `pending` holds the operands still to be evaluated in reverse.
+    Application {
+        name: String,
+        operator: Option<DataType>,
+        arguments: Vec<DataType>,
+        pending: Vec<AST>,
+        env: Rc<RefCell<Env>>,
+        span: Span
+    },
+    /// Evaluating the initializers of a `let`-family form one at a time, in
+    /// `env`. Each value is bound to `formals` in `scope`, or, for `let*` and
+    /// `let*-values`, in a new scope nested in `env`. `pending` holds the
+    /// remaining bindings in reverse.
+    Let {
+        keyword: String,
+        formals: AST,
+        pending: Vec<(AST, AST)>,
+        env: Rc<RefCell<Env>>,
+        scope: Option<Rc<RefCell<Env>>>,
+        body: AST,
+        span: Span
+    },
+    /// Waiting for the producer of a `call-with-values`.
+    Receive {
+        consumer: DataType,
+        env: Rc<RefCell<Env>>
+    },
+    /// Waiting for the `before` thunk of a `dynamic-wind`.
+    Wind {
+        before: DataType,
+        thunk: DataType,
+        after: DataType,
+        env: Rc<RefCell<Env>>
+    },
+    /// Waiting for the thunk of a `dynamic-wind`; `winder` is the one it entered.
+    Unwind {
+        winder: Rc<Winder>
+    },
+    /// Waiting for an `after` thunk, whose own value is discarded for `value`.
+    Discard {
+        value: Option<DataType>
+    },
+    /// Calling `procedure` for `map`, `for-each`, `vector-map`,
+    /// `vector-for-each` or `hash-table-walk` with each row of arguments in
+    /// turn. `pending` holds the rows still to go in reverse, and `results` the
+    /// values so far of a `map` or `vector-map`.
+    Iterate {
+        primitive: Primitive,
+        procedure: DataType,
+        pending: Vec<Vec<DataType>>,
+        results: Vec<DataType>,
+        env: Rc<RefCell<Env>>
+    },
+    /// Evaluating the expressions unquoted in the template of a `quasiquote`.
+    /// `pending` holds those still to go in reverse, and `values` the values
+    /// of the others so far.
+    Quasiquote {
+        template: AST,
+        pending: Vec<AST>,
+        values: Vec<Option<DataType>>,
+        env: Rc<RefCell<Env>>,
+        span: Span
+    },
+    /// Waiting for the comparison procedure of `member` or `assoc`, called
+    /// with `obj` and the first element of `rest`, the part of `list` still to
+    /// be searched, or with the car of that element for `assoc`.
+    Search {
+        primitive: Primitive,
+        obj: DataType,
+        compare: DataType,
+        list: DataType,
+        rest: DataType,
+        env: Rc<RefCell<Env>>
+    },
+    /// Waiting for the failure thunk of `hash-table-update!`, whose value is
+    /// the one to call `update` with for the missing `key`.
+    Update {
+        table: HashTable,
+        key: DataType,
+        update: DataType,
+        env: Rc<RefCell<Env>>
+    },
+    /// Waiting for the new value of `key` from the update procedure of
+    /// `hash-table-update!` or `hash-table-update!/default`.
+    Store {
+        primitive: Primitive,
+        table: HashTable,
+        key: DataType
+    }
+}
 
-            if list.is_empty() {
-                return Err("syntax error");
+/// Runs the evaluator from `state` until no frames are left.
+fn run(mut state: State) -> FunctionResult {
+    let run = Run::enter();
+    let winders = continuation::winders();
+    let mut stack = vec![];
+    loop {
+        let next = match state {
+            State::Eval(ast, env) => {
+                let span = ast.span();
+                eval_ast(ast, env, &mut stack).map_err(|e| e.at(span))
+            }
+            State::Apply(procedure, args, env) => apply_procedure(&procedure, args, env, "application", &mut stack),
+            State::Return(value) => match stack.pop() {
+                Some(frame) => resume(frame, value, &mut stack),
+                None => return Ok(value)
             }
+        };
+        state = match next {
+            Ok(state) => state,
+            Err(SchemeError::Throw { run: target, continuation, value }) if target == run.id => {
+                stack = continuation.frames();
+                State::Return(*value)
+            }
+            Err(e @ SchemeError::Throw { .. }) => return Err(e),
+            Err(e) => {
+                // leave any dynamic-winds the error escapes from
+                continuation::rewind(&winders)?;
+                return Err(e);
+            }
+        };
+    }
+}
 
-            tuplet!((s0,s1,s2,s3) = list);
+fn eval_ast(ast: AST, env: Rc<RefCell<Env>>, stack: &mut Vec<Frame>) -> Result<State, SchemeError> {
+    debug!("eval");
+    debug!("{:?}", ast);
+    let (list, span) = match ast {
+        AST::Symbol(s, _) => {
+            debug!("ast is a symbol: {:?}", s);
+            if s.starts_with('#') {
+                return parse_hash(&s).map(|b| State::Return(Some(b)));
+            }
+            return match env.borrow().get(&s) {
+                Some(DataType::Macro(_)) => Err(SchemeError::bad_syntax(&s, "macro keyword used as an expression")),
+                Some(data) => Ok(State::Return(Some(data))),
+                None => Err(SchemeError::UnboundVariable(s))
+            };
+        }
+        AST::String(s, _) => return Ok(State::Return(Some(DataType::string(s)))),
+        AST::Number(n, _) => return Ok(State::Return(Some(DataType::Number(n)))),
+        AST::Vector(..) | AST::Bytevector(..) => return ast2datatype(&ast).map(|v| State::Return(Some(v))),
+        AST::Children(list, span) => (list, span)
+    };
+    debug!("ast is a children: {:?}", list);
 
-            if let Some(&AST::Symbol(ref s0)) = s0 {
-                match s0.as_str() {
-                    "quote" => {
-                        debug!("quote-expression");
-                        tuplet!((s0_option,s1_option,*rest_option) = list);
+    if list.is_empty() {
+        return Err(SchemeError::bad_syntax("()", "missing procedure expression"));
+    }
 
-                        match s1_option {
-                            Some(ref ast) => {
-                                match ast2datatype(ast) {
-                                    Ok(data) => Ok(Some(data)),
-                                    Err(e) => { return Err(e); }
-                                }
-                            }
-                            None => { return Err("wrong number of parts"); }
-                        }
+    if let AST::Symbol(ref keyword, _) = list[0] {
+        match keyword.as_str() {
+            "quote" => {
+                debug!("quote-expression");
+                return match list.len() {
+                    2 => ast2datatype(&list[1]).map(|d| State::Return(Some(d))),
+                    _ => Err(SchemeError::bad_syntax("quote", "wrong number of parts"))
+                };
+            }
+            "quasiquote" => {
+                debug!("quasiquote-expression");
+                return match list.len() {
+                    2 => {
+                        let mut pending = vec![];
+                        quasiquote(&list[1], 1, &mut |expression| {
+                            pending.push(expression.clone());
+                            Ok(Some(DataType::Null))
+                        })?;
+                        pending.reverse();
+                        unquote(list[1].clone(), pending, vec![], env, span, stack)
                     }
-                    "if" => {
-                        debug!("if-expression");
-                        if let (Some(&ref cond), Some(&ref conseq), Some(&ref alt)) = (s1, s2, s3) {
-                            match eval(Some(cond.clone()), env.clone()) {
-                                Ok(Some(DataType::Bool(b))) => {
-                                    match b {
-                                        true => eval(Some(conseq.clone()), env.clone()),
-                                        false => eval(Some(alt.clone()), env.clone())
-                                    }
-                                }
-                                Ok(_) => { return Err("syntax error"); }
-                                Err(e) => { return Err(e); }
-                            }
-                        } else {
-                            return Err("wrong syntax for if expression");
+                    _ => Err(SchemeError::bad_syntax("quasiquote", "wrong number of parts"))
+                };
+            }
+            "unquote" | "unquote-splicing" => {
+                return Err(SchemeError::bad_syntax(keyword, "not in a quasiquote"));
+            }
+            "if" => {
+                debug!("if-expression");
+                let mut parts = list.into_iter().skip(1);
+                return match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                    (Some(test), Some(consequent), alternative, None) => {
+                        stack.push(Frame::If { consequent: Some(consequent), alternative, env: env.clone() });
+                        Ok(State::Eval(test, env))
+                    }
+                    _ => Err(SchemeError::bad_syntax("if", "expected (if test consequent [alternative])"))
+                };
+            }
+            "when" | "unless" => {
+                debug!("{}-expression", keyword);
+                let test = match list.get(1) {
+                    Some(test) => test.clone(),
+                    None => return Err(SchemeError::bad_syntax(keyword, &format!("expected ({} test body ...)", keyword)))
+                };
+                let body = Some(sequence(keyword, &list[2..], span)?);
+                let (consequent, alternative) = if keyword == "when" { (body, None) } else { (None, body) };
+                stack.push(Frame::If { consequent, alternative, env: env.clone() });
+                return Ok(State::Eval(test, env));
+            }
+            "cond" => {
+                debug!("cond-expression");
+                let rest = list.into_iter().skip(1).rev().collect::<Vec<AST>>();
+                return next_clause(rest, env, span, stack);
+            }
+            "case" => {
+                debug!("case-expression");
+                let mut parts = list.into_iter().skip(1);
+                return match parts.next() {
+                    Some(key) => {
+                        stack.push(Frame::Case { clauses: parts.collect(), env: env.clone(), span });
+                        Ok(State::Eval(key, env))
+                    }
+                    None => Err(SchemeError::bad_syntax("case", "expected (case key clause ...)"))
+                };
+            }
+            "and" | "or" => {
+                debug!("{}-expression", keyword);
+                let is_and = keyword == "and";
+                let mut rest = list.into_iter().skip(1).rev().collect::<Vec<AST>>();
+                return match rest.pop() {
+                    Some(first) => {
+                        if !rest.is_empty() {
+                            stack.push(if is_and {
+                                Frame::And { rest, env: env.clone() }
+                            } else {
+                                Frame::Or { rest, env: env.clone() }
+                            });
                         }
+                        Ok(State::Eval(first, env))
                     }
-                    "define" => {
-                        if let (Some(&AST::Symbol(ref s1)), Some(&ref a2)) = (s1, s2) {
-                            match a2.clone() {
-                                AST::Integer(i) => {
-                                    let env_borrow_mut = env.borrow_mut();
-                                    env_borrow_mut.local.borrow_mut().insert(s1.clone(), DataType::Number(i as f64));
-                                }
-                                AST::Float(f) => {
-                                    let env_borrow_mut = env.borrow_mut();
-                                    env_borrow_mut.local.borrow_mut().insert(s1.clone(), DataType::Number(f));
-                                }
-                                AST::Symbol(ref s) => {
-                                    if s.len() > 1 && s.starts_with("#") {
-                                        let c_option = s.chars().nth(1);
-                                        if let Some('t') = c_option {
-                                            let env_borrow_mut = env.borrow_mut();
-                                            env_borrow_mut.local.borrow_mut().insert(s1.clone(), DataType::Bool(true));
-                                        } else if let Some('f') = c_option {
-                                            let env_borrow_mut = env.borrow_mut();
-                                            env_borrow_mut.local.borrow_mut().insert(s1.clone(), DataType::Bool(false));
-                                        } else {
-                                            return Err("syntax error");
-                                        }
-                                    } else if s.starts_with("\"") && s.ends_with("\"") {
-                                        let env_borrow_mut = env.borrow_mut();
-                                        env_borrow_mut.local.borrow_mut().insert(s1.clone(), DataType::String((&s[1..s.len() - 1]).to_string()));
-                                    } else {
-                                        let data_option = env.borrow().get(&s);
-                                        if let Some(data) = data_option {
-                                            let env_borrow_mut = env.borrow_mut();
-                                            env_borrow_mut.local.borrow_mut().insert(s1.clone(), data);
-                                        } else {
-                                            return Err("symbol is not defined");
-                                        }
-                                    }
-                                }
-                                AST::Children(ref v) => {
-                                    debug!("children: {:?}", v);
-
-                                    let data_option = eval(Some(a2.clone()), env.clone());
-                                    if let Ok(Some(DataType::Lambda(ref p))) = data_option {
-                                        let env_borrow_mut = env.borrow_mut();
-                                        env_borrow_mut.local.borrow_mut().insert(s1.clone(), DataType::Lambda(p.clone()));
-                                    } else if let Ok(Some(DataType::List(ref v))) = data_option {
-                                        let env_borrow_mut = env.borrow_mut();
-                                        env_borrow_mut.local.borrow_mut().insert(s1.clone(), DataType::List(v.clone()));
-                                    } else if let Err(e) = data_option {
-                                        return Err(e);
-                                    }
-                                }
-                            }
-                            return Ok(None);
+                    None => Ok(State::Return(Some(DataType::Bool(is_and))))
+                };
+            }
+            "begin" => {
+                debug!("begin-expression");
+                let mut rest = list.into_iter().skip(1).rev().collect::<Vec<AST>>();
+                return match rest.pop() {
+                    Some(first) => {
+                        if !rest.is_empty() {
+                            stack.push(Frame::Sequence { rest, env: env.clone() });
                         }
-                        return Err("wrong syntax for define expression");
+                        Ok(State::Eval(first, env))
                     }
-                    "lambda" => {
-                        debug!("lambda-expression");
-                        if let (Some(&AST::Children(ref args)), Some(&AST::Children(ref body))) = (s1, s2) {
-                            debug!("ENV: {:?}", env);
-                            debug!("args: {:?}", args);
-                            debug!("body: {:?}", body);
-
-                            // convert args AST to Datatype symbol
-                            let args_result: Result<Vec<_>, _> = args.iter().map(|ref arg|
-                                match arg {
-                                    &&AST::Symbol(ref arg_string) => Ok(DataType::Symbol(arg_string.to_string())),
-                                    _ => Err("lambda argument must be a symbol")
-                                }
-                            ).collect();
-
-                            if let Result::Err(ref e) = args_result { return Err(e); }
-
-                            let args_meta = args_result.unwrap().iter()
-                                .map(|ref mut x| x.clone())
-                                .collect::<Vec<DataType>>();
-
-                            let local = Box::new(RefCell::new(HashMap::new()));
-                            let parent_env_box = Box::new(env.clone());
-                            let procedure_env = Env {
-                                local,
-                                parent: Some(parent_env_box)
-                            };
-
-                            debug!("procedure_env: {:?}", procedure_env);
-                            let procedure = Procedure {
-                                body: AST::Children(body.clone()),
-                                params: args_meta,
-                                env: Rc::new(RefCell::new(procedure_env))
-                            };
-                            debug!("procedure: {:?}", procedure);
-
-                            Ok(Some(DataType::Lambda(procedure)))
-                        } else {
-                            Err("syntax error")
+                    None => Ok(State::Return(None))
+                };
+            }
+            "define" => {
+                let (name, value) = definition(&list, span)?;
+                stack.push(Frame::Define { name, env: env.clone() });
+                return Ok(State::Eval(value, env));
+            }
+            "case-lambda" => {
+                debug!("case-lambda-expression");
+                let clauses = list[1..].iter().map(|clause|
+                    match *clause {
+                        AST::Children(ref clause, clause_span) if !clause.is_empty() => {
+                            let mut lambda_form = vec![AST::Symbol("lambda".to_string(), clause_span)];
+                            lambda_form.extend(clause.iter().cloned());
+                            lambda(&lambda_form, env.clone())
                         }
+                        _ => Err(SchemeError::bad_syntax("case-lambda", "expected (formals body ...)"))
                     }
-                    _ => {
-                        debug!("Some(AST::Symbol) but not define");
-                        debug!("proc_key : {}", s0);
-                        debug!("ENV: {:?}", env);
-
-                        let mut data_option = match env.borrow().get(s0) {
-                            Some(d) => Some(d.clone()),
-                            None => None
-                        };
-
-                        debug!("data_option: {:?}", data_option);
-
-                        match data_option {
-                            Some(DataType::Proc(ref f)) => {
-                                let slice = &list[1..list.len()];
-                                execute(f, slice, env)
-                            }
-                            Some(DataType::Lambda(ref mut p)) => {
-                                debug!("first elm symbol - lambda: {:?}", p);
-                                let slice = &list[1..list.len()];
-                                match prepare_arguments(slice, env.clone()) {
-                                    Ok(args) => {
-                                        debug!("first elm symbol - procedure params: {:?}", p.params);
-                                        let procedure_local = p.env.borrow_mut().local.clone();
-
-                                        for (name_ref, value_ref) in p.params.iter().zip(args.into_iter()) {
-                                            debug!("first elm symbol - procedure params - name: {:?} value: {:?}", name_ref, value_ref);
-                                            if let (Some(&DataType::Symbol(ref name)), Some(ref value)) = (Some(name_ref), Some(value_ref)) {
-                                                procedure_local.borrow_mut().insert(name.to_string(), value.clone());
-                                            } else {
-                                                unreachable!()
-                                            }
-                                        }
-
-                                        let proc_env = Env {
-                                            local: procedure_local,
-                                            parent: p.env.borrow_mut().parent.clone()
-                                        };
-
-                                        debug!("proc_env: {:?}", proc_env);
-                                        return eval(Some(p.body.clone()), Rc::new(RefCell::new(proc_env)));
-                                    }
-                                    Err(e) => return Err(e)
-                                }
-                            }
-                            Some(_) | None => Err("symbol is not defined.")
+                ).collect::<Result<Vec<Procedure>, _>>()?;
+                return Ok(State::Return(Some(DataType::CaseLambda(clauses))));
+            }
+            "set!" => {
+                let mut parts = list.into_iter().skip(1);
+                return match (parts.next(), parts.next(), parts.next()) {
+                    (Some(AST::Symbol(name, _)), Some(value), None) => {
+                        stack.push(Frame::Set { name, env: env.clone(), span });
+                        Ok(State::Eval(value, env))
+                    }
+                    _ => Err(SchemeError::bad_syntax("set!", "expected (set! name expression)"))
+                };
+            }
+            "lambda" => {
+                debug!("lambda-expression");
+                return lambda(&list, env).map(|p| State::Return(Some(DataType::Lambda(p))));
+            }
+            "define-syntax" => {
+                return match (list.get(1), list.get(2), list.len()) {
+                    (Some(AST::Symbol(name, _)), Some(spec), 3) => {
+                        let transformer = transformer(name, spec, env.clone())?;
+                        env.borrow().local.borrow_mut().insert(name.clone(), DataType::Macro(transformer));
+                        Ok(State::Return(None))
+                    }
+                    _ => Err(SchemeError::bad_syntax("define-syntax", "expected (define-syntax name transformer)"))
+                };
+            }
+            "define-macro" => {
+                let procedure = match (list.get(1), list.len()) {
+                    (Some(AST::Children(formals, formals_span)), n) if n >= 3 => match formals.first() {
+                        Some(AST::Symbol(name, _)) => {
+                            let mut lambda_form = vec![
+                                AST::Symbol("lambda".to_string(), span),
+                                params(&formals[1..], *formals_span)
+                            ];
+                            lambda_form.extend(list[2..].iter().cloned());
+                            Some((name.clone(), DataType::Lambda(lambda(&lambda_form, env.clone())?)))
                         }
+                        _ => None
+                    },
+                    (Some(AST::Symbol(name, _)), 3) => match eval(Some(list[2].clone()), env.clone())? {
+                        Some(ref procedure) if procedure.is_procedure() => Some((name.clone(), procedure.clone())),
+                        Some(other) => return Err(SchemeError::wrong_type("define-macro", "procedure", &other)),
+                        None => None
+                    },
+                    _ => None
+                };
+                return match procedure {
+                    Some((name, procedure)) => {
+                        env.borrow().local.borrow_mut().insert(name, DataType::Macro(Macro::Procedural(Box::new(procedure))));
+                        Ok(State::Return(None))
                     }
-                }
-            } else {
-                debug!("first ast is not a symbol");
-                debug!("proc_key : {:?}", s0);
-
-                tuplet!((s0_option,*rest_option) = list);
-
-                if let Some(&AST::Children(_)) = s0_option {
-                    match eval(Some(list.first().unwrap().clone()), env.clone()) {
-                        Ok(Some(DataType::Proc(ref f))) => {
-                            debug!("first elm function - function: {:?}", f);
-                            match rest_option {
-                                Some(rest) => execute(f, rest, env),
-                                None => execute(f, &vec![], env)
+                    None => Err(SchemeError::bad_syntax("define-macro", "expected (define-macro (name params ...) body)"))
+                };
+            }
+            "let" | "let*" | "letrec" | "letrec*" | "let-values" | "let*-values" => {
+                debug!("{}-expression", keyword);
+                return let_form(keyword, &list, span, env, stack);
+            }
+            "let-syntax" | "letrec-syntax" => {
+                debug!("{}-expression", keyword);
+                let bindings = match list.get(1) {
+                    Some(AST::Children(bindings, _)) => bindings,
+                    _ => return Err(SchemeError::bad_syntax(keyword, "expected a list of bindings"))
+                };
+                let mut macros = HashMap::new();
+                for binding in bindings {
+                    match *binding {
+                        AST::Children(ref binding, _) if binding.len() == 2 => match binding[0] {
+                            AST::Symbol(ref name, _) => {
+                                let transformer = transformer(name, &binding[1], env.clone())?;
+                                macros.insert(name.clone(), DataType::Macro(transformer));
                             }
-                        }
-                        Ok(Some(DataType::Lambda(ref mut p))) => {
-                            debug!("first elm lambda - lambda: {:?} - procedure params: {:?}", p, p.params);
-                            let proc_env = match rest_option {
-                                Some(rest) => {
-                                    match prepare_arguments(rest, env.clone()) {
-                                        Ok(args) => {
-                                            let p_env_borrow_mut = p.env.borrow_mut();
-                                            for (name_ref, value_ref) in p.params.iter().zip(args.into_iter()) {
-                                                debug!("first elm lambda - procedure params - name: {:?} value: {:?}", name_ref, value_ref);
-                                                if let (Some(&DataType::Symbol(ref name)), Some(ref value)) = (Some(name_ref), Some(value_ref)) {
-                                                    p_env_borrow_mut.local.borrow_mut().insert(name.to_string(), value.clone());
-                                                } else {
-                                                    unreachable!()
-                                                }
-                                            }
-                                            Env {
-                                                local: p_env_borrow_mut.local.clone(),
-                                                parent: p_env_borrow_mut.parent.clone()
-                                            }
-                                        }
-                                        Err(e) => return Err(e)
-                                    }
-                                }
-                                None => {
-                                    let p_env_borrow_mut = p.env.borrow_mut();
-                                    Env {
-                                        local: p_env_borrow_mut.local.clone(),
-                                        parent: p_env_borrow_mut.parent.clone()
-                                    }
-                                }
-                            };
-                            debug!("proc_env: {:?}", proc_env);
-                            return eval(Some(p.body.clone()), Rc::new(RefCell::new(proc_env)));
-                        }
-                        Ok(_) => { return Err("unsupported data type on first element"); }
-                        Err(e) => { return Err(e); }
+                            _ => return Err(SchemeError::bad_syntax(keyword, "macro name must be a symbol"))
+                        },
+                        _ => return Err(SchemeError::bad_syntax(keyword, "expected (name transformer)"))
                     }
-                } else {
-                    return Err("syntax error");
                 }
+                let scope = Env {
+                    local: Box::new(RefCell::new(macros)),
+                    parent: Some(Box::new(env))
+                };
+                let mut body = vec![AST::Symbol("begin".to_string(), span)];
+                body.extend(list.into_iter().skip(2));
+                return Ok(State::Eval(AST::Children(body, span), Rc::new(RefCell::new(scope))));
             }
+            _ => {}
         }
-        Some(_) | None => {
-            debug!("ast is not a symbol/children");
-            let data = match ast_option {
-                Some(AST::Integer(i)) => Some(DataType::Number(i as f64)),
-                Some(AST::Float(f)) => Some(DataType::Number(f)),
-                Some(_) => unreachable!(),
-                None => None
-            };
-            Ok(data)
-        }
+    }
+
+    // a symbol operator is looked up here, as it may name a macro
+    let head = match list[0] {
+        AST::Symbol(ref s, head_span) if !s.starts_with('#') => match env.borrow().get(s) {
+            Some(DataType::Macro(transformer)) => {
+                debug!("macro use: {}", s);
+                return Ok(State::Eval(transformer.expand(&list, span, env.clone())?, env.clone()));
+            }
+            Some(procedure) => Some(procedure),
+            None => return Err(SchemeError::UnboundVariable(s.clone()).at(head_span))
+        },
+        _ => None
+    };
+    let name = match list[0] {
+        AST::Symbol(ref s, _) => s.clone(),
+        _ => "application".to_string()
+    };
+    let mut pending = list.into_iter().rev().collect::<Vec<AST>>();
+    let operator = pending.pop().unwrap();
+    stack.push(Frame::Application { name, operator: None, arguments: vec![], pending, env: env.clone(), span });
+    match head {
+        Some(procedure) => Ok(State::Return(Some(procedure))),
+        None => Ok(State::Eval(operator, env))
     }
 }
 
-fn prepare_arguments(arguments: &[AST], env: Rc<RefCell<Env>>) -> Result<Vec<DataType>, &'static str> {
-    let args_result: Result<Vec<_>, _> = arguments.iter()
-        .map(|x| eval(Some(x.clone()), env.clone()))
-        .collect();
-    debug!("args: {:?}", args_result);
-    if let Result::Err(ref e) = args_result { return Err(e); }
+/// Whether `value` counts as true in a test: everything but `#f` does.
+fn is_true(value: &DataType) -> bool {
+    *value != DataType::Bool(false)
+}
 
-    let args = args_result.unwrap().iter()
-        .filter(|x| x.is_some())
-        .flat_map(|ref mut x| x.clone())
-        .collect::<Vec<DataType>>();
-    Ok(args)
+/// Whether `a` and `b` are the same in the sense of `eqv?`.
+fn eqv(a: &DataType, b: &DataType) -> bool {
+    match (a, b) {
+        (DataType::Pair(a), DataType::Pair(b)) => a.ptr_eq(b),
+        (DataType::String(a), DataType::String(b)) => a.ptr_eq(b),
+        (DataType::Vector(a), DataType::Vector(b)) => a.ptr_eq(b),
+        (DataType::Bytevector(a), DataType::Bytevector(b)) => a.ptr_eq(b),
+        (DataType::Number(a), DataType::Number(b)) => a.eqv(b),
+        _ => a == b
+    }
 }
 
-fn execute(f: &Function, arguments: &[AST], env: Rc<RefCell<Env>>) -> Result<Option<DataType>, &'static str> {
-    match prepare_arguments(arguments, env.clone()) {
-        Ok(args) => {
-            f.call(args, env.clone()).and_then(|r| {
-                match r {
-                    Some(data) => Ok(Some(data)),
-                    None => Ok(None)
+/// Whether `a` and `b` are the same in the sense of `equal?`. Pairs and
+/// vectors are compared element by element, without recursion, and two of them
+/// met again while comparing are taken to be equal, so circular structures can
+/// be compared too.
+fn equal(a: &DataType, b: &DataType) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![(a.clone(), b.clone())];
+    while let Some((a, b)) = pending.pop() {
+        match (&a, &b) {
+            (DataType::Pair(x), DataType::Pair(y)) => {
+                if x.ptr_eq(y) || !seen.insert((address(&a), address(&b))) {
+                    continue;
                 }
-            })
+                pending.push((x.cdr(), y.cdr()));
+                pending.push((x.car(), y.car()));
+            }
+            (DataType::Vector(x), DataType::Vector(y)) => {
+                if x.ptr_eq(y) || !seen.insert((address(&a), address(&b))) {
+                    continue;
+                }
+                if x.len() != y.len() {
+                    return false;
+                }
+                pending.extend(x.items().into_iter().zip(y.items()));
+            }
+            _ => if a != b {
+                return false;
+            }
         }
-        Err(e) => return Err(e)
     }
+    true
 }
 
-pub fn setup() -> HashMap<String, DataType> {
-    let mut map = HashMap::new();
-    map.insert("pi".to_string(), DataType::Number(std::f64::consts::PI));
+/// The address of a pair or vector, which identifies it while it is alive.
+fn address(value: &DataType) -> Option<usize> {
+    match *value {
+        DataType::Pair(ref p) => Some(Rc::as_ptr(&p.0) as *const () as usize),
+        DataType::Vector(ref v) => Some(Rc::as_ptr(&v.0) as *const () as usize),
+        _ => None
+    }
+}
 
-    map.insert("+".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
-        debug!("Function - name: {:?} - Args: {:?}", "+", vec);
-        let is_numbers = vec.iter().all(|&ref x| if let &DataType::Number(_) = x { true } else { false });
-        if !is_numbers {
-            return Err("wrong argument datatype");
+/// Moves on to the first of the `cond` clauses `rest`, which are in reverse.
+fn next_clause(mut rest: Vec<AST>, env: Rc<RefCell<Env>>, span: Span,
+               stack: &mut Vec<Frame>) -> Result<State, SchemeError> {
+    let clause = match rest.pop() {
+        Some(AST::Children(clause, _)) => clause,
+        Some(_) => return Err(SchemeError::bad_syntax("cond", "expected (test body ...)")),
+        None => return Ok(State::Return(None))
+    };
+    match clause.first() {
+        Some(AST::Symbol(s, _)) if s == "else" => {
+            if !rest.is_empty() {
+                return Err(SchemeError::bad_syntax("cond", "else must be the last clause"));
+            }
+            Ok(State::Eval(sequence("cond", &clause[1..], span)?, env))
         }
+        Some(test) => {
+            let test = test.clone();
+            stack.push(Frame::Cond { clause, rest, env: env.clone(), span });
+            Ok(State::Eval(test, env))
+        }
+        None => Err(SchemeError::bad_syntax("cond", "expected (test body ...)"))
+    }
+}
 
-        let desc = vec.iter().map(|&ref x|
-            match x {
-                &DataType::Number(f) => f.to_string(),
-                _ => unreachable!(),
+/// Continues with the body of the selected `cond` or `case` clause: either a
+/// sequence of expressions, or `=> receiver` to call `receiver` with `value`.
+fn clause_body(keyword: &str, body: &[AST], value: Option<DataType>, env: Rc<RefCell<Env>>, span: Span,
+               stack: &mut Vec<Frame>) -> Result<State, SchemeError> {
+    match body.first() {
+        Some(AST::Symbol(s, _)) if s == "=>" => match body.get(1) {
+            Some(receiver) if body.len() == 2 => {
+                let arguments = value.into_iter().collect();
+                stack.push(Frame::Application {
+                    name: keyword.to_string(), operator: None, arguments, pending: vec![], env: env.clone(), span
+                });
+                Ok(State::Eval(receiver.clone(), env))
             }
-        ).collect::<Vec<String>>().join(" + ");
-        debug!("Description: {}", desc);
-        let numbers = vec.iter().filter_map(|&ref x| { if let &DataType::Number(ref y) = x { Some(y.clone()) } else { None } });
-        let data: f64 = numbers.map(|x| {
-            let y: f64 = x.clone().into();
-            y
-        }).sum();
-        Ok(Some(DataType::Number(data)))
-    }))));
+            _ => Err(SchemeError::bad_syntax(keyword, "expected => receiver"))
+        },
+        _ => Ok(State::Eval(sequence(keyword, body, span)?, env))
+    }
+}
 
-    map.insert("-".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
-        debug!("Function - name: {:?} - Args: {:?}", "+", vec);
-        let is_numbers = vec.iter().all(|&ref x| if let &DataType::Number(_) = x { true } else { false });
+fn new_scope(env: &Rc<RefCell<Env>>) -> Rc<RefCell<Env>> {
+    Rc::new(RefCell::new(Env {
+        local: Box::new(RefCell::new(HashMap::new())),
+        parent: Some(Box::new(env.clone()))
+    }))
+}
+
+/// Whether `ast` is the `.` of a dotted list.
+fn is_dot(ast: &AST) -> bool {
+    matches!(*ast, AST::Symbol(ref s, _) if s == ".")
+}
+
+/// The formals of a procedure whose name and formals were written as `(name . params)`.
+fn params(params: &[AST], span: Span) -> AST {
+    match params.len() {
+        2 if is_dot(&params[0]) => params[1].clone(),
+        _ => AST::Children(params.to_vec(), span)
+    }
+}
 
-        if !is_numbers {
-            return Err("wrong argument datatype");
+/// Splits `(define name expression)` into the name and the expression. The
+/// sugar `(define (name . params) body ...)`, which may be curried as in
+/// `(define ((name a) b) body ...)`, is turned into a `lambda` expression.
+fn definition(list: &[AST], span: Span) -> Result<(String, AST), SchemeError> {
+    match list.get(1) {
+        Some(AST::Symbol(name, _)) if list.len() == 3 => Ok((name.clone(), list[2].clone())),
+        Some(AST::Children(target, target_span)) if list.len() >= 3 && !target.is_empty() => {
+            let mut lambda_form = vec![AST::Symbol("lambda".to_string(), span), params(&target[1..], *target_span)];
+            lambda_form.extend(list[2..].iter().cloned());
+            let define_form = [list[0].clone(), target[0].clone(), AST::Children(lambda_form, span)];
+            definition(&define_form, span)
         }
+        _ => Err(SchemeError::bad_syntax("define", "expected (define name expression) or (define (name params ...) body ...)"))
+    }
+}
 
-        let desc = vec.iter().map(|&ref x|
-            match x {
-                &DataType::Number(f) => f.to_string(),
-                _ => unreachable!(),
+/// The body of a `lambda` or `let`-family form as a single expression. The
+/// definitions it starts with become a `letrec*` around the rest of it.
+fn body(keyword: &str, forms: &[AST], span: Span) -> Result<AST, SchemeError> {
+    let mut bindings = vec![];
+    let mut rest = forms;
+    while let Some(AST::Children(form, form_span)) = rest.first() {
+        match form.first() {
+            Some(AST::Symbol(s, _)) if s == "define" => {
+                let (name, value) = definition(form, *form_span)?;
+                bindings.push(AST::Children(vec![AST::Symbol(name, *form_span), value], *form_span));
+                rest = &rest[1..];
             }
-        ).collect::<Vec<String>>().join(" - ");
-        debug!("Description: {}", desc);
-
-        let value: f64 = vec.iter().filter_map(|&ref x| { if let &DataType::Number(ref y) = x { Some(y.clone()) } else { None } })
-            .map(|x| {
-                let y: f64 = x.clone().into();
-                y
-            })
-            .fold(0.0, |mut acc, x| {
-                if acc == 0.0 { acc = x; } else { acc = acc - x; }
-                acc
-            });
-        Ok(Some(DataType::Number(value)))
+            _ => break
+        }
+    }
+    if bindings.is_empty() {
+        return sequence(keyword, forms, span);
+    }
+    if rest.is_empty() {
+        return Err(SchemeError::bad_syntax(keyword, "body has no expression after its definitions"));
+    }
+    let mut letrec = vec![AST::Symbol("letrec*".to_string(), span), AST::Children(bindings, span)];
+    letrec.extend(rest.iter().cloned());
+    Ok(AST::Children(letrec, span))
+}
 
-    }))));
+/// A sequence of expressions, such as a `when` body, as a single expression.
+fn sequence(keyword: &str, body: &[AST], span: Span) -> Result<AST, SchemeError> {
+    if body.is_empty() {
+        return Err(SchemeError::bad_syntax(keyword, "missing body"));
+    }
+    let mut forms = vec![AST::Symbol("begin".to_string(), span)];
+    forms.extend(body.iter().cloned());
+    Ok(AST::Children(forms, span))
+}
 
-    map.insert("*".to_string(), DataType::Proc(
-        Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
-            debug!("Function - name: {:?} - Args: {:?}", "*", vec);
-            let is_numbers = vec.iter().all(|&ref x| if let &DataType::Number(_) = x { true } else { false });
-            if !is_numbers {
-                return Err("wrong argument datatype");
+/// Starts evaluating `let`, `let*`, `letrec`, `letrec*`, `let-values`,
+/// `let*-values` or a named `let`.
+fn let_form(keyword: &str, list: &[AST], span: Span, env: Rc<RefCell<Env>>,
+            stack: &mut Vec<Frame>) -> Result<State, SchemeError> {
+    let usage = || SchemeError::bad_syntax(keyword, &format!("expected ({} (bindings ...) body ...)", keyword));
+    let (loop_name, bindings, body) = match (list.get(1), list.get(2)) {
+        (Some(AST::Symbol(name, _)), Some(AST::Children(bindings, _))) if keyword == "let" => {
+            (Some(name.clone()), bindings, &list[3..])
+        }
+        (Some(AST::Children(bindings, _)), _) => (None, bindings, &list[2..]),
+        _ => return Err(usage())
+    };
+    let body = self::body(keyword, body, span)?;
+    let multiple = keyword.ends_with("-values");
+    let mut pairs = bindings.iter().map(|binding|
+        match *binding {
+            AST::Children(ref binding, _) if binding.len() == 2 && (multiple || matches!(binding[0], AST::Symbol(..))) => {
+                Ok((binding[0].clone(), binding[1].clone()))
             }
+            _ => Err(usage())
+        }
+    ).collect::<Result<Vec<(AST, AST)>, _>>()?;
+
+    if let Some(name) = loop_name {
+        // a named let calls a procedure bound to `name` in its own body
+        let scope = new_scope(&env);
+        let params = pairs.iter().map(|pair| pair.0.clone()).collect::<Vec<AST>>();
+        let procedure = lambda(&[AST::Symbol("lambda".to_string(), span), AST::Children(params, span), body], scope.clone())?;
+        scope.borrow().local.borrow_mut().insert(name.clone(), DataType::Lambda(procedure.clone()));
+        let pending = pairs.into_iter().rev().map(|pair| pair.1).collect();
+        stack.push(Frame::Application { name, operator: None, arguments: vec![], pending, env, span });
+        return Ok(State::Return(Some(DataType::Lambda(procedure))));
+    }
 
-            let desc = vec.iter().map(|&ref x|
-                match x {
-                    &DataType::Number(f) => f.to_string(),
-                    _ => panic!("Something went wrong"),
-                }
-            ).collect::<Vec<String>>().join(" x ");
-            debug!("Description: {}", desc);
+    let (env, scope) = match keyword {
+        "let" | "let-values" => (env.clone(), Some(new_scope(&env))),
+        "letrec" | "letrec*" => {
+            let scope = new_scope(&env);
+            (scope.clone(), Some(scope))
+        }
+        _ => (env, None)
+    };
+    pairs.reverse();
+    match pairs.pop() {
+        Some((formals, init)) => {
+            stack.push(Frame::Let { keyword: keyword.to_string(), formals, pending: pairs, env: env.clone(), scope, body, span });
+            Ok(State::Eval(init, env))
+        }
+        None => Ok(State::Eval(body, scope.unwrap_or_else(|| new_scope(&env))))
+    }
+}
 
-            let numbers = vec.iter().filter_map(|&ref x| { if let &DataType::Number(ref y) = x { Some(y.clone()) } else { None } });
-            let data: f64 = numbers.map(|x| {
-                let y: f64 = x.clone().into();
-                y
-            }).product();
-            Ok(Some(DataType::Number(data)))
-        }))));
+/// Binds `value` to the variable `formals` of a `let`-family form, or, for
+/// `let-values` and `let*-values`, the values in `value` to the formals list.
+fn bind_formals(keyword: &str, formals: &AST, value: Option<DataType>, scope: &Rc<RefCell<Env>>) -> Result<(), SchemeError> {
+    let scope = scope.borrow();
+    let mut local = scope.local.borrow_mut();
+    if let AST::Symbol(ref name, _) = *formals {
+        if !keyword.ends_with("-values") {
+            local.insert(name.clone(), value.unwrap_or(DataType::Unspecified));
+            return Ok(());
+        }
+    }
 
-    map.insert("/".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
-        debug!("Function - name: {:?} - Args: {:?}", "/", vec);
-        let is_numbers = vec.iter().all(|&ref x| if let &DataType::Number(_) = x { true } else { false });
+    let values = match value {
+        Some(DataType::Values(values)) => values,
+        Some(value) => vec![value],
+        None => vec![]
+    };
+    let (names, rest) = self::formals(keyword, formals)?;
+    let expected = match rest {
+        Some(_) => Arity::AtLeast(names.len()),
+        None => Arity::Exactly(names.len())
+    };
+    check_arity(keyword, &values, expected)?;
 
-        if !is_numbers {
-            return Err("wrong argument datatype");
-        }
+    let mut values = values.into_iter();
+    for (name, value) in names.into_iter().zip(values.by_ref()) {
+        local.insert(name, value);
+    }
+    if let Some(rest) = rest {
+        local.insert(rest, DataType::list(values.collect()));
+    }
+    Ok(())
+}
 
-        let desc = vec.iter().map(|&ref x|
-            match x {
-                &DataType::Number(f) => f.to_string(),
-                _ => unreachable!(),
+/// Reads the formals of a procedure or of `let-values`: `(a b)`, `(a b . rest)`
+/// or `args`, as the names of the required parameters and of the rest parameter.
+fn formals(keyword: &str, formals: &AST) -> Result<(Vec<String>, Option<String>), SchemeError> {
+    let symbol = |ast: &AST| match *ast {
+        AST::Symbol(ref s, _) if s != "." => Ok(s.clone()),
+        _ => Err(SchemeError::bad_syntax(keyword, "formals must be symbols"))
+    };
+    let (names, rest) = match *formals {
+        AST::Symbol(..) => return Ok((vec![], Some(symbol(formals)?))),
+        AST::Children(ref items, _) => match items.len() {
+            n if n >= 2 && is_dot(&items[n - 2]) => {
+                (&items[..n - 2], Some(symbol(&items[n - 1])?))
             }
-        ).collect::<Vec<String>>().join(" / ");
-        debug!("Description: {}", desc);
-
-        let value: f64 = vec.iter().filter_map(|&ref x| { if let &DataType::Number(ref y) = x { Some(y.clone()) } else { None } })
-            .map(|x| {
-                let y: f64 = x.clone().into();
-                y
-            })
-            .fold(0.0, |mut acc, x| {
-                if acc == 0.0 { acc = x; } else { acc = acc / x; }
-                acc
-            });
-        Ok(Some(DataType::Number(value)))
-    }))));
+            _ => (&items[..], None)
+        },
+        _ => return Err(SchemeError::bad_syntax(keyword, "formals must be symbols"))
+    };
+    let names = names.iter().map(symbol).collect::<Result<Vec<String>, _>>()?;
+    if names.iter().enumerate().any(|(i, name)| names[..i].contains(name) || rest.as_ref() == Some(name)) {
+        return Err(SchemeError::bad_syntax(keyword, "duplicate parameter"));
+    }
+    Ok((names, rest))
+}
 
-    define_comparison!(gt, ">", |a,b| { a > b });
-    map.insert(">".to_string(), gt);
+/// The macro `define-syntax`, `let-syntax` or `letrec-syntax` binds `name` to:
+/// either a `syntax-rules` form, or an expression evaluating to a transformer
+/// such as one made by `er-macro-transformer`.
+fn transformer(name: &str, spec: &AST, env: Rc<RefCell<Env>>) -> Result<Macro, SchemeError> {
+    match *spec {
+        AST::Children(ref parts, _) if matches!(parts.first(), Some(AST::Symbol(s, _)) if s == "syntax-rules") => {
+            SyntaxRules::new(name, spec).map(Macro::SyntaxRules)
+        }
+        _ => match eval(Some(spec.clone()), env)? {
+            Some(DataType::Macro(transformer)) => Ok(transformer),
+            Some(other) => Err(SchemeError::wrong_type(name, "macro transformer", &other)),
+            None => Err(SchemeError::bad_syntax(name, "transformer expression has no value"))
+        }
+    }
+}
 
-    define_comparison!(lt, "<", |a,b| { a < b });
+/// Hands `value` to `frame`, the innermost pending frame.
+fn resume(frame: Frame, value: Option<DataType>, stack: &mut Vec<Frame>) -> Result<State, SchemeError> {
+    match frame {
+        Frame::If { consequent, alternative, env } => {
+            // a test with no value is not #f, so it counts as true
+            let branch = match value {
+                Some(ref test) if !is_true(test) => alternative,
+                _ => consequent
+            };
+            match branch {
+                Some(branch) => Ok(State::Eval(branch, env)),
+                None => Ok(State::Return(None))
+            }
+        }
+        Frame::Cond { clause, rest, env, span } => match value {
+            Some(ref test) if !is_true(test) => next_clause(rest, env, span, stack),
+            _ if clause.len() == 1 => Ok(State::Return(value)),
+            _ => clause_body("cond", &clause[1..], value, env, span, stack)
+        },
+        Frame::Case { clauses, env, span } => {
+            let key = value.unwrap_or(DataType::Unspecified);
+            for (i, clause) in clauses.iter().enumerate() {
+                let clause = match *clause {
+                    AST::Children(ref clause, _) if clause.len() >= 2 => clause,
+                    _ => return Err(SchemeError::bad_syntax("case", "expected ((datum ...) body ...)").at(span))
+                };
+                let selected = match clause[0] {
+                    AST::Symbol(ref s, _) if s == "else" && i + 1 == clauses.len() => true,
+                    AST::Children(ref data, _) => {
+                        let data = data.iter().map(ast2datatype).collect::<Result<Vec<_>, _>>()?;
+                        data.iter().any(|datum| eqv(datum, &key))
+                    }
+                    _ => return Err(SchemeError::bad_syntax("case", "expected ((datum ...) body ...)").at(span))
+                };
+                if selected {
+                    return clause_body("case", &clause[1..], Some(key), env, span, stack);
+                }
+            }
+            Ok(State::Return(None))
+        }
+        Frame::And { mut rest, env } => match value {
+            Some(DataType::Bool(false)) => Ok(State::Return(value)),
+            _ => {
+                let next = rest.pop().unwrap();
+                if !rest.is_empty() {
+                    stack.push(Frame::And { rest, env: env.clone() });
+                }
+                Ok(State::Eval(next, env))
+            }
+        },
+        Frame::Or { mut rest, env } => match value {
+            Some(ref test) if is_true(test) => Ok(State::Return(value)),
+            _ => {
+                let next = rest.pop().unwrap();
+                if !rest.is_empty() {
+                    stack.push(Frame::Or { rest, env: env.clone() });
+                }
+                Ok(State::Eval(next, env))
+            }
+        },
+        Frame::Sequence { mut rest, env } => {
+            let next = rest.pop().unwrap();
+            if !rest.is_empty() {
+                stack.push(Frame::Sequence { rest, env: env.clone() });
+            }
+            Ok(State::Eval(next, env))
+        }
+        Frame::Define { name, env } => {
+            let env_borrow_mut = env.borrow_mut();
+            env_borrow_mut.local.borrow_mut().insert(name, value.unwrap_or(DataType::Unspecified));
+            Ok(State::Return(None))
+        }
+        Frame::Set { name, env, span } => {
+            if env.borrow().set(&name, value.unwrap_or(DataType::Unspecified)) {
+                Ok(State::Return(None))
+            } else {
+                Err(SchemeError::UnboundVariable(name).at(span))
+            }
+        }
+        Frame::Application { name, mut operator, mut arguments, mut pending, env, span } => {
+            match (operator.is_some(), value) {
+                (true, argument) => arguments.push(argument.unwrap_or(DataType::Unspecified)),
+                (false, Some(procedure)) => operator = Some(procedure),
+                (false, None) => return Err(SchemeError::bad_syntax("application", "operator has no value").at(span))
+            }
+            match pending.pop() {
+                Some(next) => {
+                    stack.push(Frame::Application { name, operator, arguments, pending, env: env.clone(), span });
+                    Ok(State::Eval(next, env))
+                }
+                None => {
+                    let procedure = operator.unwrap();
+                    apply_procedure(&procedure, arguments, env, &name, stack).map_err(|e| e.at(span))
+                }
+            }
+        }
+        Frame::Wind { before, thunk, after, env } => {
+            let winder = Rc::new(Winder::new(before, after, env.clone(), continuation::winders()));
+            continuation::set_winders(Some(winder.clone()));
+            stack.push(Frame::Unwind { winder });
+            Ok(State::Apply(thunk, vec![], env))
+        }
+        Frame::Unwind { winder } => {
+            continuation::set_winders(winder.parent.clone());
+            stack.push(Frame::Discard { value });
+            Ok(State::Apply(winder.after.clone(), vec![], winder.env.clone()))
+        }
+        Frame::Let { keyword, formals, mut pending, env, scope, body, span } => {
+            let target = scope.clone().unwrap_or_else(|| new_scope(&env));
+            bind_formals(&keyword, &formals, value, &target).map_err(|e| e.at(span))?;
+            let env = if scope.is_some() { env } else { target.clone() };
+            match pending.pop() {
+                Some((formals, init)) => {
+                    stack.push(Frame::Let { keyword, formals, pending, env: env.clone(), scope, body, span });
+                    Ok(State::Eval(init, env))
+                }
+                None => Ok(State::Eval(body, target))
+            }
+        }
+        Frame::Receive { consumer, env } => {
+            let args = match value {
+                Some(DataType::Values(values)) => values,
+                Some(value) => vec![value],
+                None => vec![]
+            };
+            Ok(State::Apply(consumer, args, env))
+        }
+        Frame::Discard { value } => Ok(State::Return(value)),
+        Frame::Iterate { primitive, procedure, pending, mut results, env } => {
+            if matches!(primitive, Primitive::Map | Primitive::VectorMap) {
+                results.push(value.unwrap_or(DataType::Unspecified));
+            }
+            iterate(primitive, procedure, pending, results, env, stack)
+        }
+        Frame::Quasiquote { template, pending, mut values, env, span } => {
+            values.push(value);
+            unquote(template, pending, values, env, span, stack)
+        }
+        Frame::Search { primitive, obj, compare, list, rest, env } => {
+            // a comparison with no value is not #f, so it counts as a match
+            if value.is_none_or(|v| is_true(&v)) {
+                return Ok(State::Return(Some(match (primitive, &rest) {
+                    (Primitive::Assoc, DataType::Pair(pair)) => pair.car(),
+                    _ => rest
+                })));
+            }
+            let rest = match rest {
+                DataType::Pair(ref pair) => pair.cdr(),
+                _ => unreachable!()
+            };
+            search(primitive, obj, compare, list, rest, env, stack)
+        }
+        Frame::Update { table, key, update, env } => {
+            stack.push(Frame::Store { primitive: Primitive::HashTableUpdate, table, key });
+            Ok(State::Apply(update, value.into_iter().collect(), env))
+        }
+        Frame::Store { primitive, table, key } => {
+            let name = if primitive == Primitive::HashTableUpdate { "hash-table-update!" } else { "hash-table-update!/default" };
+            match value {
+                Some(value) => table.set(name, key, value)?,
+                None => return Err(SchemeError::bad_syntax(name, "update procedure returned no value"))
+            }
+            Ok(State::Return(None))
+        }
+    }
+}
+
+/// Calls `procedure`. Calling a lambda does not run its body but returns the
+/// state that will, which is what makes calls in tail position proper tail calls.
+/// `name` is how the procedure was referred to, for error messages.
+fn apply_procedure(procedure: &DataType, args: Vec<DataType>, env: Rc<RefCell<Env>>, name: &str,
+                   stack: &mut Vec<Frame>) -> Result<State, SchemeError> {
+    debug!("apply: {:?} - Args: {:?}", procedure, args);
+    match *procedure {
+        DataType::Proc(ref f) => f.call(args, env).map(State::Return),
+        DataType::Lambda(ref p) => Ok(State::Eval(p.body.clone(), procedure_env(p, args, name)?)),
+        DataType::CaseLambda(ref clauses) => {
+            match clauses.iter().find(|p| p.arity().accepts(args.len())) {
+                Some(p) => Ok(State::Eval(p.body.clone(), procedure_env(p, args, name)?)),
+                None => {
+                    let min = clauses.iter().map(|p| p.params.len()).min().unwrap_or(0);
+                    let max = clauses.iter().map(|p| p.params.len()).max().unwrap_or(0);
+                    let expected = match clauses.iter().any(|p| p.rest.is_some()) {
+                        true => Arity::AtLeast(min),
+                        false if min == max => Arity::Exactly(min),
+                        false => Arity::Between(min, max)
+                    };
+                    Err(SchemeError::arity(name, expected, args.len()))
+                }
+            }
+        }
+        DataType::Primitive(Primitive::Apply) => {
+            check_arity("apply", &args, Arity::AtLeast(2))?;
+            let mut args = args;
+            let last = args.pop().unwrap();
+            let spread = last.list_items().ok_or_else(|| SchemeError::wrong_type("apply", "list", &last))?;
+            let procedure = args.remove(0);
+            args.extend(spread);
+            apply_procedure(&procedure, args, env, "apply", stack)
+        }
+        DataType::Primitive(Primitive::CallCC) => {
+            check_arity("call/cc", &args, Arity::Exactly(1))?;
+            let k = Continuation::capture(stack.clone());
+            let mut args = args;
+            Ok(State::Apply(args.remove(0), vec![DataType::Continuation(k)], env))
+        }
+        DataType::Primitive(Primitive::CallWithValues) => {
+            check_arity("call-with-values", &args, Arity::Exactly(2))?;
+            let mut args = args.into_iter();
+            let (producer, consumer) = (args.next().unwrap(), args.next().unwrap());
+            stack.push(Frame::Receive { consumer, env: env.clone() });
+            Ok(State::Apply(producer, vec![], env))
+        }
+        DataType::Primitive(Primitive::DynamicWind) => {
+            check_arity("dynamic-wind", &args, Arity::Exactly(3))?;
+            let mut args = args.into_iter();
+            let (before, thunk, after) = (args.next().unwrap(), args.next().unwrap(), args.next().unwrap());
+            stack.push(Frame::Wind { before: before.clone(), thunk, after, env: env.clone() });
+            Ok(State::Apply(before, vec![], env))
+        }
+        DataType::Primitive(primitive @ (Primitive::Map | Primitive::ForEach | Primitive::VectorMap |
+                                         Primitive::VectorForEach | Primitive::HashTableWalk)) => {
+            let (procedure, mut pending) = iteration(primitive, args)?;
+            pending.reverse();
+            iterate(primitive, procedure, pending, vec![], env, stack)
+        }
+        DataType::Primitive(primitive @ (Primitive::Member | Primitive::Assoc)) => {
+            let name = if primitive == Primitive::Member { "member" } else { "assoc" };
+            check_arity(name, &args, Arity::Between(2, 3))?;
+            let mut args = args.into_iter();
+            let (obj, list) = (args.next().unwrap(), args.next().unwrap());
+            let compare = match args.next() {
+                Some(compare) => compare,
+                None if primitive == Primitive::Member => return member(name, &obj, &list, |a, b| Ok(a == b)).map(State::Return),
+                None => return assoc(name, &obj, &list, |a, b| Ok(a == b)).map(State::Return)
+            };
+            if primitive == Primitive::Assoc && list.list_items().is_none() {
+                return Err(SchemeError::wrong_type(name, "list", &list));
+            }
+            search(primitive, obj, compare, list.clone(), list, env, stack)
+        }
+        DataType::Primitive(Primitive::HashTableRef) => {
+            check_arity("hash-table-ref", &args, Arity::Between(2, 4))?;
+            let table = hash_tables("hash-table-ref", &args[..1])?.remove(0);
+            // (hash-table-ref table key [failure [success]]): failure is a thunk
+            // called for a missing key, and success is called with the value found
+            match (table.get("hash-table-ref", &args[1])?, args.get(2), args.get(3)) {
+                (Some(value), _, Some(success)) => Ok(State::Apply(success.clone(), vec![value], env)),
+                (Some(value), _, None) => Ok(State::Return(Some(value))),
+                (None, Some(failure), _) => Ok(State::Apply(failure.clone(), vec![], env)),
+                (None, None, _) => Err(SchemeError::wrong_type("hash-table-ref", "key in the table", &args[1]))
+            }
+        }
+        DataType::Primitive(Primitive::HashTableUpdate) => {
+            check_arity("hash-table-update!", &args, Arity::Between(3, 4))?;
+            let table = hash_tables("hash-table-update!", &args[..1])?.remove(0);
+            let mut args = args.into_iter().skip(1);
+            let (key, update, failure) = (args.next().unwrap(), args.next().unwrap(), args.next());
+            // (hash-table-update! table key update [failure]), where failure gives
+            // the value to update when the key is missing
+            match (table.get("hash-table-update!", &key)?, failure) {
+                (Some(value), _) => {
+                    stack.push(Frame::Store { primitive: Primitive::HashTableUpdate, table, key });
+                    Ok(State::Apply(update, vec![value], env))
+                }
+                (None, Some(failure)) => {
+                    stack.push(Frame::Update { table, key, update, env: env.clone() });
+                    Ok(State::Apply(failure, vec![], env))
+                }
+                (None, None) => Err(SchemeError::wrong_type("hash-table-update!", "key in the table", &key))
+            }
+        }
+        DataType::Primitive(Primitive::HashTableUpdateDefault) => {
+            check_arity("hash-table-update!/default", &args, Arity::Exactly(4))?;
+            let table = hash_tables("hash-table-update!/default", &args[..1])?.remove(0);
+            let mut args = args.into_iter().skip(1);
+            let (key, update, default) = (args.next().unwrap(), args.next().unwrap(), args.next().unwrap());
+            let current = table.get("hash-table-update!/default", &key)?.unwrap_or(default);
+            stack.push(Frame::Store { primitive: Primitive::HashTableUpdateDefault, table, key });
+            Ok(State::Apply(update, vec![current], env))
+        }
+        DataType::Continuation(ref k) => {
+            check_arity("continuation", &args, Arity::Between(0, 1))?;
+            let run = k.target().ok_or(SchemeError::ExpiredContinuation)?;
+            continuation::rewind(k.winders())?;
+            Err(SchemeError::Throw {
+                run,
+                continuation: k.clone(),
+                value: Box::new(args.into_iter().next())
+            })
+        }
+        ref other => Err(SchemeError::wrong_type(name, "procedure", other))
+    }
+}
+
+/// Checks the arguments of a `map`-like primitive, and returns the procedure it
+/// calls along with the arguments of each call.
+fn iteration(primitive: Primitive, mut args: Vec<DataType>) -> Result<(DataType, Vec<Vec<DataType>>), SchemeError> {
+    let (name, procedure, rows) = match primitive {
+        Primitive::Map | Primitive::ForEach => {
+            let name = if primitive == Primitive::Map { "map" } else { "for-each" };
+            check_arity(name, &args, Arity::AtLeast(2))?;
+            (name, 0, lists(name, &args[1..])?)
+        }
+        Primitive::VectorMap | Primitive::VectorForEach => {
+            let name = if primitive == Primitive::VectorMap { "vector-map" } else { "vector-for-each" };
+            check_arity(name, &args, Arity::AtLeast(2))?;
+            let columns = vectors(name, &args[1..])?.iter().map(Vector::items).collect::<Vec<_>>();
+            (name, 0, rows(&columns))
+        }
+        Primitive::HashTableWalk => {
+            check_arity("hash-table-walk", &args, Arity::Exactly(2))?;
+            // the entries are collected first, so the procedure may change the table
+            let entries = hash_tables("hash-table-walk", &args[..1])?.remove(0).entries();
+            ("hash-table-walk", 1, entries.into_iter().map(|(key, value)| vec![key, value]).collect())
+        }
+        _ => unreachable!()
+    };
+    if !args[procedure].is_procedure() {
+        return Err(SchemeError::wrong_type(name, "procedure", &args[procedure]));
+    }
+    Ok((args.swap_remove(procedure), rows))
+}
+
+/// Calls `procedure` with the next of the `pending` rows of arguments of a
+/// `map`-like primitive, or, once there are none left, returns what it gives:
+/// the `results` as a list for `map` and as a vector for `vector-map`.
+fn iterate(primitive: Primitive, procedure: DataType, mut pending: Vec<Vec<DataType>>, results: Vec<DataType>,
+           env: Rc<RefCell<Env>>, stack: &mut Vec<Frame>) -> Result<State, SchemeError> {
+    match pending.pop() {
+        Some(row) => {
+            stack.push(Frame::Iterate { primitive, procedure: procedure.clone(), pending, results, env: env.clone() });
+            Ok(State::Apply(procedure, row, env))
+        }
+        None => Ok(State::Return(match primitive {
+            Primitive::Map => Some(DataType::list(results)),
+            Primitive::VectorMap => Some(DataType::Vector(Vector::new(results))),
+            _ => None
+        }))
+    }
+}
+
+/// Calls `compare` for `member` or `assoc` with `obj` and the first element of
+/// `rest`, or its car for `assoc`, or returns `#f` once `rest` is empty.
+/// `list` is the whole list being searched, for the error raised if it is improper.
+fn search(primitive: Primitive, obj: DataType, compare: DataType, list: DataType, rest: DataType,
+          env: Rc<RefCell<Env>>, stack: &mut Vec<Frame>) -> Result<State, SchemeError> {
+    let name = if primitive == Primitive::Member { "member" } else { "assoc" };
+    let item = match rest {
+        DataType::Pair(ref pair) => pair.car(),
+        DataType::Null => return Ok(State::Return(Some(DataType::Bool(false)))),
+        _ => return Err(SchemeError::wrong_type(name, "list", &list))
+    };
+    let item = match (primitive, item) {
+        (Primitive::Assoc, DataType::Pair(entry)) => entry.car(),
+        (Primitive::Assoc, other) => return Err(SchemeError::wrong_type(name, "pair", &other)),
+        (_, item) => item
+    };
+    stack.push(Frame::Search { primitive, obj: obj.clone(), compare: compare.clone(), list, rest, env: env.clone() });
+    Ok(State::Apply(compare, vec![obj, item], env))
+}
+
+/// The environment a call to `p` with `args` runs its body in: a fresh frame
+/// holding the parameters, whose parent is the environment `p` was defined in.
+/// `name` is how the procedure was referred to, for the error raised when
+/// `args` does not match its parameters.
+fn procedure_env(p: &Procedure, args: Vec<DataType>, name: &str) -> Result<Rc<RefCell<Env>>, SchemeError> {
+    debug!("procedure params: {:?}", p.params);
+    check_arity(name, &args, p.arity())?;
+    let mut procedure_local = HashMap::new();
+    let mut args = args.into_iter();
+    for (name_ref, value_ref) in p.params.iter().zip(args.by_ref()) {
+        debug!("procedure params - name: {:?} value: {:?}", name_ref, value_ref);
+        if let DataType::Symbol(ref name) = *name_ref {
+            procedure_local.insert(name.to_string(), value_ref);
+        } else {
+            unreachable!()
+        }
+    }
+    if let Some(ref rest) = p.rest {
+        procedure_local.insert(rest.clone(), DataType::list(args.collect()));
+    }
+
+    let proc_env = Env {
+        local: Box::new(RefCell::new(procedure_local)),
+        parent: Some(Box::new(p.env.clone()))
+    };
+    debug!("proc_env: {:?}", proc_env);
+    Ok(Rc::new(RefCell::new(proc_env)))
+}
+
+fn lambda(list: &[AST], env: Rc<RefCell<Env>>) -> Result<Procedure, SchemeError> {
+    if let (Some(args), true) = (list.get(1), list.len() >= 3) {
+        debug!("ENV: {:?}", env);
+        debug!("args: {:?}", args);
+
+        // convert args AST to Datatype symbol
+        let (names, rest) = formals("lambda", args)?;
+        let args_meta = names.into_iter().map(DataType::Symbol).collect::<Vec<DataType>>();
+        let body = body("lambda", &list[2..], list[0].span().to(list[list.len() - 1].span()))?;
+        debug!("body: {:?}", body);
+
+        let procedure = Procedure {
+            body,
+            params: args_meta,
+            rest,
+            env
+        };
+        debug!("procedure: {:?}", procedure);
+        Ok(procedure)
+    } else {
+        Err(SchemeError::bad_syntax("lambda", "expected (lambda formals body ...)"))
+    }
+}
+
+/// Evaluates the next of the `pending` expressions unquoted in the template of a
+/// `quasiquote`, or, once there are none left, fills the template in with their `values`.
+fn unquote(template: AST, mut pending: Vec<AST>, values: Vec<Option<DataType>>, env: Rc<RefCell<Env>>, span: Span,
+           stack: &mut Vec<Frame>) -> Result<State, SchemeError> {
+    match pending.pop() {
+        Some(expression) => {
+            stack.push(Frame::Quasiquote { template, pending, values, env: env.clone(), span });
+            Ok(State::Eval(expression, env))
+        }
+        None => {
+            let mut values = values.into_iter();
+            quasiquote(&template, 1, &mut |_| Ok(values.next().unwrap()))
+                .map(|d| State::Return(Some(d)))
+                .map_err(|e| e.at(span))
+        }
+    }
+}
+
+/// The datum the template of a `quasiquote` nested `depth` levels deep stands
+/// for. `unquoted` gives the value of each expression unquoted in it, in order.
+fn quasiquote(template: &AST, depth: usize,
+              unquoted: &mut dyn FnMut(&AST) -> FunctionResult) -> Result<DataType, SchemeError> {
+    let list = match *template {
+        AST::Children(ref list, _) => list,
+        AST::Vector(ref items, span) => {
+            let items = quasiquote(&AST::Children(items.clone(), span), depth, unquoted)?;
+            return Ok(DataType::Vector(Vector::new(items.list_items().unwrap_or_default())));
+        }
+        _ => return ast2datatype(template)
+    };
+
+    if let (Some(AST::Symbol(keyword, _)), 2) = (list.first(), list.len()) {
+        let inner_depth = match keyword.as_str() {
+            "unquote" if depth == 1 => {
+                return Ok(unquoted(&list[1])?.unwrap_or(DataType::Unspecified));
+            }
+            "unquote-splicing" if depth == 1 => {
+                return Err(SchemeError::bad_syntax("unquote-splicing", "must appear inside a list"));
+            }
+            "unquote" | "unquote-splicing" => Some(depth - 1),
+            "quasiquote" => Some(depth + 1),
+            _ => None
+        };
+        if let Some(inner_depth) = inner_depth {
+            return Ok(DataType::list(vec![
+                DataType::Symbol(keyword.clone()),
+                quasiquote(&list[1], inner_depth, unquoted)?
+            ]));
+        }
+    }
+
+    let (list, tail) = match list.len() {
+        n if n >= 2 && is_dot(&list[n - 2]) => (&list[..n - 2], Some(&list[n - 1])),
+        _ => (&list[..], None)
+    };
+    let mut result = vec![];
+    for item in list {
+        match *item {
+            AST::Children(ref splice, _) if depth == 1 && splice.len() == 2 &&
+                matches!(splice[0], AST::Symbol(ref keyword, _) if keyword == "unquote-splicing") => {
+                match unquoted(&splice[1])? {
+                    Some(other) => match other.list_items() {
+                        Some(items) => result.extend(items),
+                        None => return Err(SchemeError::wrong_type("unquote-splicing", "list", &other))
+                    },
+                    None => return Err(SchemeError::bad_syntax("unquote-splicing", "expression has no value"))
+                }
+            }
+            _ => result.push(quasiquote(item, depth, unquoted)?)
+        }
+    }
+    let tail = match tail {
+        Some(tail) => quasiquote(tail, depth, unquoted)?,
+        None => DataType::Null
+    };
+    Ok(DataType::list_with_tail(result, tail))
+}
+
+fn check_arity(procedure: &str, vec: &[DataType], expected: Arity) -> Result<(), SchemeError> {
+    if expected.accepts(vec.len()) {
+        Ok(())
+    } else {
+        Err(SchemeError::arity(procedure, expected, vec.len()))
+    }
+}
+
+fn numbers(procedure: &str, vec: &[DataType]) -> Result<Vec<Number>, SchemeError> {
+    vec.iter().map(|x|
+        match *x {
+            DataType::Number(ref n) => Ok(n.clone()),
+            ref other => Err(SchemeError::wrong_type(procedure, "number", other))
+        }
+    ).collect()
+}
+
+fn reals(procedure: &str, vec: &[DataType]) -> Result<Vec<Number>, SchemeError> {
+    vec.iter().map(|x|
+        match *x {
+            DataType::Number(ref n) if n.is_real() => Ok(n.clone()),
+            ref other => Err(SchemeError::wrong_type(procedure, "real number", other))
+        }
+    ).collect()
+}
+
+fn chars(procedure: &str, vec: &[DataType]) -> Result<Vec<char>, SchemeError> {
+    vec.iter().map(|x|
+        match *x {
+            DataType::Char(c) => Ok(c),
+            ref other => Err(SchemeError::wrong_type(procedure, "character", other))
+        }
+    ).collect()
+}
+
+/// `c` mapped by a case conversion, or `c` itself if the conversion does not
+/// give a single character, as for `ß` to upper case.
+fn convert_case<I: Iterator<Item = char>>(c: char, mut converted: I) -> char {
+    match (converted.next(), converted.next()) {
+        (Some(single), None) => single,
+        _ => c
+    }
+}
+
+/// The zero of every run of ten decimal digits (general category Nd) as of
+/// Unicode 15.0; each run holds the digits 0 through 9 in order.
+const DECIMAL_ZEROS: [u32; 68] = [
+    0x30, 0x660, 0x6F0, 0x7C0, 0x966, 0x9E6, 0xA66, 0xAE6, 0xB66, 0xBE6, 0xC66, 0xCE6, 0xD66, 0xDE6,
+    0xE50, 0xED0, 0xF20, 0x1040, 0x1090, 0x17E0, 0x1810, 0x1946, 0x19D0, 0x1A80, 0x1A90, 0x1B50,
+    0x1BB0, 0x1C40, 0x1C50, 0xA620, 0xA8D0, 0xA900, 0xA9D0, 0xA9F0, 0xAA50, 0xABF0, 0xFF10, 0x104A0,
+    0x10D30, 0x11066, 0x110F0, 0x11136, 0x111D0, 0x112F0, 0x11450, 0x114D0, 0x11650, 0x116C0,
+    0x11730, 0x118E0, 0x11950, 0x11C50, 0x11D50, 0x11DA0, 0x11F50, 0x16A60, 0x16AC0, 0x16B50,
+    0x1D7CE, 0x1D7D8, 0x1D7E2, 0x1D7EC, 0x1D7F6, 0x1E140, 0x1E2F0, 0x1E4F0, 0x1E950, 0x1FBF0
+];
+
+/// The value of `c` as a decimal digit, or `None` if it is not one. Other
+/// numeric characters, such as `½` or Roman numerals, are not digits.
+fn digit_value(c: char) -> Option<u32> {
+    let code = c as u32;
+    DECIMAL_ZEROS.iter()
+        .find(|&&zero| zero <= code && code < zero + 10)
+        .map(|zero| code - zero)
+}
+
+fn vectors(procedure: &str, vec: &[DataType]) -> Result<Vec<Vector>, SchemeError> {
+    vec.iter().map(|x|
+        match *x {
+            DataType::Vector(ref v) => Ok(v.clone()),
+            ref other => Err(SchemeError::wrong_type(procedure, "vector", other))
+        }
+    ).collect()
+}
+
+fn bytevectors(procedure: &str, vec: &[DataType]) -> Result<Vec<Bytevector>, SchemeError> {
+    vec.iter().map(|x|
+        match *x {
+            DataType::Bytevector(ref b) => Ok(b.clone()),
+            ref other => Err(SchemeError::wrong_type(procedure, "bytevector", other))
+        }
+    ).collect()
+}
+
+fn byte(procedure: &str, value: &DataType) -> Result<u8, SchemeError> {
+    match *value {
+        DataType::Number(Number::Integer(n)) if (0..256).contains(&n) => Ok(n as u8),
+        ref other => Err(SchemeError::wrong_type(procedure, "byte", other))
+    }
+}
+
+/// Whether the endianness argument of a multi-byte bytevector accessor, the
+/// symbol `big` or `little`, asks for big-endian order.
+fn big_endian(procedure: &str, value: &DataType) -> Result<bool, SchemeError> {
+    match *value {
+        DataType::Symbol(ref s) if s == "big" => Ok(true),
+        DataType::Symbol(ref s) if s == "little" => Ok(false),
+        ref other => Err(SchemeError::wrong_type(procedure, "endianness (big or little)", other))
+    }
+}
+
+fn hash_tables(procedure: &str, vec: &[DataType]) -> Result<Vec<HashTable>, SchemeError> {
+    vec.iter().map(|x|
+        match *x {
+            DataType::HashTable(ref t) => Ok(t.clone()),
+            ref other => Err(SchemeError::wrong_type(procedure, "hash table", other))
+        }
+    ).collect()
+}
+
+fn strings(procedure: &str, vec: &[DataType]) -> Result<Vec<SchemeString>, SchemeError> {
+    vec.iter().map(|x|
+        match *x {
+            DataType::String(ref s) => Ok(s.clone()),
+            ref other => Err(SchemeError::wrong_type(procedure, "string", other))
+        }
+    ).collect()
+}
+
+/// The optional `start` and `end` arguments at `vec[from..]` of a procedure
+/// working on part of a string or vector of `len` elements, defaulting to the
+/// whole of it. `expected` describes a valid index in errors.
+fn range(procedure: &str, vec: &[DataType], from: usize, len: usize,
+         expected: &'static str) -> Result<(usize, usize), SchemeError> {
+    let end = match vec.get(from + 1) {
+        Some(arg) => match index(procedure, arg)? {
+            end if end <= len => end,
+            _ => return Err(SchemeError::wrong_type(procedure, expected, arg))
+        },
+        None => len
+    };
+    let start = match vec.get(from) {
+        Some(arg) => match index(procedure, arg)? {
+            start if start <= end => start,
+            _ => return Err(SchemeError::wrong_type(procedure, expected, arg))
+        },
+        None => 0
+    };
+    Ok((start, end))
+}
+
+/// The characters of `s` from index `start` up to `end`.
+fn substring(s: &str, start: usize, end: usize) -> String {
+    s.chars().skip(start).take(end - start).collect()
+}
+
+/// `s` case folded for case-insensitive comparison. Final sigma is folded like any other sigma.
+fn foldcase(s: &str) -> String {
+    s.chars().flat_map(char::to_lowercase).map(|c| if c == 'ς' { 'σ' } else { c }).collect()
+}
+
+/// The largest (`Ordering::Greater`) or smallest (`Ordering::Less`) of `numbers`,
+/// which is inexact if any of them is. NaNs are ignored unless all are NaN.
+fn extremum(numbers: Vec<Number>, wanted: Ordering) -> Number {
+    let exact = numbers.iter().all(Number::is_exact);
+    let best = numbers.into_iter()
+        .fold(None, |best: Option<Number>, x| match best {
+            Some(ref b) if x.compare(b) != Some(wanted) && !b.to_f64().is_nan() => best.clone(),
+            _ => Some(x)
+        })
+        .unwrap();
+    if exact { best } else { best.to_inexact() }
+}
+
+/// An argument used as an index or a count, which must be a non-negative exact integer.
+fn index(procedure: &str, value: &DataType) -> Result<usize, SchemeError> {
+    match *value {
+        DataType::Number(Number::Integer(n)) if n >= 0 => Ok(n as usize),
+        ref other => Err(SchemeError::wrong_type(procedure, "non-negative exact integer", other))
+    }
+}
+
+/// What is left of `list` after dropping `k` pairs, or `None` if it runs out of pairs first.
+fn list_tail(list: &DataType, k: usize) -> Option<DataType> {
+    let mut rest = list.clone();
+    for _ in 0..k {
+        rest = match rest {
+            DataType::Pair(ref pair) => pair.cdr(),
+            _ => return None
+        };
+    }
+    Some(rest)
+}
+
+/// The elements of each of the lists `vec`, cut down to the length of the shortest one.
+fn lists(procedure: &str, vec: &[DataType]) -> Result<Vec<Vec<DataType>>, SchemeError> {
+    let lists = vec.iter()
+        .map(|list| list.list_items().ok_or_else(|| SchemeError::wrong_type(procedure, "list", list)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows(&lists))
+}
+
+/// The arguments of each call a `map`-like procedure makes over `columns`:
+/// the i-th elements of each, for as long as none of them has run out.
+fn rows(columns: &[Vec<DataType>]) -> Vec<Vec<DataType>> {
+    let len = columns.iter().map(Vec::len).min().unwrap_or(0);
+    (0..len).map(|i| columns.iter().map(|column| column[i].clone()).collect()).collect()
+}
+
+/// The first sublist of `list` whose car is the same as `obj`, for `memq`,
+/// `memv` and `member`.
+fn member<F>(procedure: &str, obj: &DataType, list: &DataType, mut same: F) -> FunctionResult
+    where F: FnMut(&DataType, &DataType) -> Result<bool, SchemeError> {
+    let mut rest = list.clone();
+    loop {
+        rest = match rest {
+            DataType::Pair(ref pair) => {
+                if same(obj, &pair.car())? {
+                    return Ok(Some(rest.clone()));
+                }
+                pair.cdr()
+            }
+            DataType::Null => return Ok(Some(DataType::Bool(false))),
+            _ => return Err(SchemeError::wrong_type(procedure, "list", list))
+        };
+    }
+}
+
+/// The first pair of the association list `alist` whose car is the same as
+/// `obj`, for `assq`, `assv` and `assoc`.
+fn assoc<F>(procedure: &str, obj: &DataType, alist: &DataType, mut same: F) -> FunctionResult
+    where F: FnMut(&DataType, &DataType) -> Result<bool, SchemeError> {
+    let entries = alist.list_items().ok_or_else(|| SchemeError::wrong_type(procedure, "list", alist))?;
+    for entry in entries {
+        match entry {
+            DataType::Pair(ref pair) => if same(obj, &pair.car())? {
+                return Ok(Some(entry.clone()));
+            },
+            ref other => return Err(SchemeError::wrong_type(procedure, "pair", other))
+        }
+    }
+    Ok(Some(DataType::Bool(false)))
+}
+
+pub fn setup() -> HashMap<String, DataType> {
+    let mut map = HashMap::new();
+    map.insert("pi".to_string(), DataType::Number(Number::Real(std::f64::consts::PI)));
+
+    map.insert("+".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "+", vec);
+        let numbers = numbers("+", &vec)?;
+
+        let desc = numbers.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(" + ");
+        debug!("Description: {}", desc);
+        let data = numbers.iter().fold(Number::Integer(0), |acc, x| acc.add(x));
+        Ok(Some(DataType::Number(data)))
+    }))));
+
+    map.insert("-".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "-", vec);
+        check_arity("-", &vec, Arity::AtLeast(1))?;
+        let numbers = numbers("-", &vec)?;
+
+        let desc = numbers.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(" - ");
+        debug!("Description: {}", desc);
+
+        let value = match numbers.split_first() {
+            Some((first, [])) => first.neg(),
+            Some((first, rest)) => rest.iter().fold(first.clone(), |acc, x| acc.sub(x)),
+            None => unreachable!()
+        };
+        Ok(Some(DataType::Number(value)))
+
+    }))));
+
+    map.insert("*".to_string(), DataType::Proc(
+        Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+            debug!("Function - name: {:?} - Args: {:?}", "*", vec);
+            let numbers = numbers("*", &vec)?;
+
+            let desc = numbers.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(" x ");
+            debug!("Description: {}", desc);
+
+            let data = numbers.iter().fold(Number::Integer(1), |acc, x| acc.mul(x));
+            Ok(Some(DataType::Number(data)))
+        }))));
+
+    map.insert("/".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "/", vec);
+        check_arity("/", &vec, Arity::AtLeast(1))?;
+        let numbers = numbers("/", &vec)?;
+
+        let desc = numbers.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(" / ");
+        debug!("Description: {}", desc);
+
+        let (first, rest) = match numbers.split_first() {
+            Some((first, [])) => (Number::Integer(1), std::slice::from_ref(first)),
+            Some((first, rest)) => (first.clone(), rest),
+            None => unreachable!()
+        };
+        let value = rest.iter().try_fold(first, |acc, x| acc.div(x))
+            .ok_or_else(|| SchemeError::DivisionByZero("/".to_string()))?;
+        Ok(Some(DataType::Number(value)))
+    }))));
+
+    define_comparison!(gt, ">", |o| { o == Ordering::Greater });
+    map.insert(">".to_string(), gt);
+
+    define_comparison!(lt, "<", |o| { o == Ordering::Less });
     map.insert("<".to_string(), lt);
 
-    define_comparison!(eq, "=", |a,b| { a == b });
+    define_comparison!(eq, "=", numbers, |o| { o == Ordering::Equal });
     map.insert("=".to_string(), eq);
 
-    define_comparison!(ge, ">=", |a,b| { a >= b });
+    define_comparison!(ge, ">=", |o| { o != Ordering::Less });
     map.insert(">=".to_string(), ge);
 
-    define_comparison!(le, "<=", |a,b| { a <= b });
+    define_comparison!(le, "<=", |o| { o != Ordering::Greater });
     map.insert("<=".to_string(), le);
 
     map.insert("abs".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "abs", vec);
-        if vec.len() != 1 {
-            return Err("abs function requires one argument only");
-        }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("abs function unknown argument type");
-        }
-        match value_option.unwrap() {
-            &DataType::Number(f) => Ok(Some(DataType::Number(f.abs()))),
-            _ => Err("abs function requires an argument of type 'number'")
+        check_arity("abs", &vec, Arity::Exactly(1))?;
+        let numbers = reals("abs", &vec)?;
+        Ok(Some(DataType::Number(numbers[0].abs())))
+    }))));
+
+    define_numeric!(sqrt, "sqrt", Number::sqrt);
+    map.insert("sqrt".to_string(), sqrt);
+
+    define_numeric!(exp, "exp", Number::exp);
+    map.insert("exp".to_string(), exp);
+
+    map.insert("log".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "log", vec);
+        check_arity("log", &vec, Arity::Between(1, 2))?;
+        let numbers = numbers("log", &vec)?;
+        match numbers.get(1) {
+            Some(base) => numbers[0].log().div(&base.log())
+                .map(|n| Some(DataType::Number(n)))
+                .ok_or_else(|| SchemeError::DivisionByZero("log".to_string())),
+            None => Ok(Some(DataType::Number(numbers[0].log())))
         }
     }))));
 
-    map.insert("append".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
-        debug!("Function - name: {:?} - Args: {:?}", "append", vec);
+    map.insert("make-rectangular".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "make-rectangular", vec);
+        check_arity("make-rectangular", &vec, Arity::Exactly(2))?;
+        let numbers = reals("make-rectangular", &vec)?;
+        Ok(Some(DataType::Number(Number::rectangular(&numbers[0], &numbers[1]))))
+    }))));
 
-        if vec.is_empty() {
-            return Ok(Some(DataType::List(vec![])));
-        }
-
-        if vec.len() == 1 {
-            let value_option = vec.first();
-            return match value_option {
-                Some(&DataType::List(ref l)) => Ok(Some(DataType::List(l.clone()))),
-                Some(&DataType::Number(n)) => Ok(Some(DataType::Number(n))),
-                Some(&DataType::Bool(b)) => Ok(Some(DataType::Bool(b))),
-                Some(&DataType::Symbol(ref s)) => Ok(Some(DataType::Symbol(s.clone()))),
-                Some(&DataType::String(ref s)) => Ok(Some(DataType::String(s.clone()))),
-                Some(&DataType::Proc(ref p)) => Ok(Some(DataType::Proc(p.clone()))),
-                Some(&DataType::Lambda(ref l)) => Ok(Some(DataType::Lambda(l.clone()))),
-                Some(&DataType::Pair(ref p)) => Ok(Some(DataType::Pair((p.0.clone(), p.1.clone())))),
-                None => { return Err("append function unknown argument type"); }
-            };
-        }
+    map.insert("make-polar".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "make-polar", vec);
+        check_arity("make-polar", &vec, Arity::Exactly(2))?;
+        let numbers = reals("make-polar", &vec)?;
+        Ok(Some(DataType::Number(Number::polar(&numbers[0], &numbers[1]))))
+    }))));
 
-        //        let first_option = vec.first();
-        tuplet!((first_option,*rest_option) = vec);
-
-        match first_option {
-            Some(&DataType::List(ref l1)) => {
-                let mut list = l1.clone();
-
-                match rest_option {
-                    Some(rest) => {
-                        for item in rest.iter() {
-                            match item {
-                                &DataType::List(ref l2) => list.append(&mut l2.clone()),
-                                &DataType::Number(n) => {
-                                    return Ok(Some(
-                                        DataType::Pair(
-                                            (Box::new(DataType::List(list.clone())),
-                                             Box::new(DataType::Number(n)))
-                                        )
-                                    ))
-                                },
-                                &DataType::Bool(b) => {
-                                    return Ok(Some(
-                                        DataType::Pair(
-                                            (Box::new(DataType::List(list.clone())),
-                                             Box::new(DataType::Bool(b)))
-                                        )
-                                    ))
-                                },
-                                &DataType::Pair(ref p) => {
-                                    list.push((*p.0).clone());
-                                    return Ok(Some(
-                                        DataType::Pair(
-                                            (Box::new(DataType::List(list.clone())),
-                                             p.1.clone())
-                                        )
-                                    ))
-                                },
-                                &DataType::Symbol(ref s) => {
-                                    return Ok(Some(
-                                        DataType::Pair(
-                                            (Box::new(DataType::List(list.clone())),
-                                             Box::new(DataType::Symbol(s.clone())))
-                                        )
-                                    ))
-                                },
-                                &DataType::String(ref s) => {
-                                    return Ok(Some(
-                                        DataType::Pair(
-                                            (Box::new(DataType::List(list.clone())),
-                                             Box::new(DataType::String(s.clone())))
-                                        )
-                                    ))
-                                },
-                                &DataType::Proc(ref p) => {
-                                    return Ok(Some(
-                                        DataType::Pair(
-                                            (Box::new(DataType::List(list.clone())),
-                                             Box::new(DataType::Proc(p.clone())))
-                                        )
-                                    ))
-                                },
-                                &DataType::Lambda(ref l) => {
-                                    return Ok(Some(
-                                        DataType::Pair(
-                                            (Box::new(DataType::List(list.clone())),
-                                             Box::new(DataType::Lambda(l.clone())))
-                                        )
-                                    ))
-                                }
-                            }
-                        }
-                    }
-                    None => {
-                        return Err("append function requires an argument of type 'list'");
-                    }
-                }
+    define_numeric!(real_part, "real-part", Number::real_part);
+    map.insert("real-part".to_string(), real_part);
 
-                Ok(Some(DataType::List(list.clone())))
-            }
-            Some(_) => { return Err("append function wrong type of the first argument"); }
-            None => { return Err("append function unknown argument type"); }
+    define_numeric!(imag_part, "imag-part", Number::imag_part);
+    map.insert("imag-part".to_string(), imag_part);
+
+    define_numeric!(magnitude, "magnitude", Number::magnitude);
+    map.insert("magnitude".to_string(), magnitude);
+
+    define_numeric!(angle, "angle", Number::angle);
+    map.insert("angle".to_string(), angle);
+
+    map.insert("exact".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "exact", vec);
+        check_arity("exact", &vec, Arity::Exactly(1))?;
+        match vec[0] {
+            DataType::Number(ref n) => match n.to_exact() {
+                Some(exact) => Ok(Some(DataType::Number(exact))),
+                None => Err(SchemeError::wrong_type("exact", "finite real number", &vec[0]))
+            },
+            ref other => Err(SchemeError::wrong_type("exact", "number", other))
         }
     }))));
 
-    map.insert("apply".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, env: Rc<RefCell<Env>>| {
-        debug!("Function - name: {:?} - Args: {:?}", "apply", vec);
+    map.insert("inexact".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "inexact", vec);
+        check_arity("inexact", &vec, Arity::Exactly(1))?;
+        match vec[0] {
+            DataType::Number(ref n) => Ok(Some(DataType::Number(n.to_inexact()))),
+            ref other => Err(SchemeError::wrong_type("inexact", "number", other))
+        }
+    }))));
 
-        if vec.len() != 2 {
-            return Err("apply function requires two arguments");
+    map.insert("exact?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "exact?", vec);
+        check_arity("exact?", &vec, Arity::Exactly(1))?;
+        match vec[0] {
+            DataType::Number(ref n) => Ok(Some(DataType::Bool(n.is_exact()))),
+            ref other => Err(SchemeError::wrong_type("exact?", "number", other))
         }
+    }))));
 
-        tuplet!((s0,s1) = vec);
-        if let Some(&DataType::List(ref args)) = s1 {
-            match s0 {
-                Some(&DataType::Proc(ref f)) => {
-                    f.call(args.clone(), env.clone()).and_then(|r| {
-                        match r {
-                            Some(data) => Ok(Some(data)),
-                            None => Ok(None)
-                        }
-                    })
-                }
-                Some(&DataType::Lambda(ref p)) => {
-                    debug!("first elm symbol - lambda: {:?}", p);
-                    debug!("first elm symbol - procedure params: {:?}", p.params);
-                    let procedure_local = p.env.borrow_mut().local.clone();
-
-                    for (name_ref, value_ref) in p.params.iter().zip(args.into_iter()) {
-                        debug!("first elm symbol - procedure params - name: {:?} value: {:?}", name_ref, value_ref);
-                        if let (Some(&DataType::Symbol(ref name)), Some(value)) = (Some(name_ref), Some(value_ref)) {
-                            procedure_local.borrow_mut().insert(name.to_string(), value.clone());
-                        } else {
-                            unreachable!()
-                        }
-                    }
+    map.insert("inexact?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "inexact?", vec);
+        check_arity("inexact?", &vec, Arity::Exactly(1))?;
+        match vec[0] {
+            DataType::Number(ref n) => Ok(Some(DataType::Bool(!n.is_exact()))),
+            ref other => Err(SchemeError::wrong_type("inexact?", "number", other))
+        }
+    }))));
 
-                    let proc_env = Env {
-                        local: procedure_local,
-                        parent: p.env.borrow_mut().parent.clone()
-                    };
+    map.insert("integer?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "integer?", vec);
+        check_arity("integer?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::Number(ref n) if n.is_integer()))))
+    }))));
 
-                    debug!("proc_env: {:?}", proc_env);
-                    return eval(Some(p.body.clone()), Rc::new(RefCell::new(proc_env)));
-                }
-                Some(_) | None => Err("apply function unknown first argument type")
-            }
-        } else {
-            return Err("apply function requires two arguments");
+    map.insert("append".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "append", vec);
+
+        // every argument but the last is copied, and the last one becomes the tail
+        let mut vec = vec;
+        let mut result = match vec.pop() {
+            Some(last) => last,
+            None => return Ok(Some(DataType::Null))
+        };
+        for list in vec.iter().rev() {
+            let items = list.list_items().ok_or_else(|| SchemeError::wrong_type("append", "list", list))?;
+            result = DataType::list_with_tail(items, result);
         }
+        Ok(Some(result))
     }))));
 
-    // pre-defined commands
-    map.insert("begin".to_string(), DataType::Proc(
-        Function(
-            Rc::new(|mut vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
-                debug!("Function - name: {:?} - Args: {:?}", "begin", vec);
-                Ok(vec.pop().clone())
-            })
-        )
-    ));
+    map.insert("apply".to_string(), DataType::Primitive(Primitive::Apply));
+    map.insert("call-with-current-continuation".to_string(), DataType::Primitive(Primitive::CallCC));
+    map.insert("call/cc".to_string(), DataType::Primitive(Primitive::CallCC));
+    map.insert("dynamic-wind".to_string(), DataType::Primitive(Primitive::DynamicWind));
+    map.insert("call-with-values".to_string(), DataType::Primitive(Primitive::CallWithValues));
+
+    map.insert("values".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "values", vec);
+        let mut vec = vec;
+        match vec.len() {
+            1 => Ok(vec.pop()),
+            _ => Ok(Some(DataType::Values(vec)))
+        }
+    }))));
+
+    map.insert("er-macro-transformer".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "er-macro-transformer", vec);
+        check_arity("er-macro-transformer", &vec, Arity::Exactly(1))?;
+        if !vec[0].is_procedure() {
+            return Err(SchemeError::wrong_type("er-macro-transformer", "procedure", &vec[0]));
+        }
+        Ok(Some(DataType::Macro(Macro::ExplicitRenaming(Box::new(vec[0].clone())))))
+    }))));
 
+    // pre-defined commands
     map.insert("car".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "car", vec);
-        if vec.len() != 1 {
-            return Err("car function requires one argument only");
-        }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("car function unknown argument type");
-        }
-        match value_option.unwrap() {
-            &DataType::List(ref vec) => {
-                let value = vec.first();
-                if value.is_some() {
-                    Ok(Some(DataType::from(value.unwrap().clone())))
-                } else {
-                    Err("car function requires a non-empty list")
-                }
-            }
-            &DataType::Pair(ref p) => Ok(Some(*(p.0).clone())),
-            _ => Err("car function requires an argument of type 'list' / 'pair'")
+        check_arity("car", &vec, Arity::Exactly(1))?;
+        match vec[0] {
+            DataType::Pair(ref p) => Ok(Some(p.car())),
+            ref other => Err(SchemeError::wrong_type("car", "pair", other))
         }
     }))));
 
     map.insert("cdr".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "cdr", vec);
-        if vec.len() != 1 {
-            return Err("cdr function requires one argument only");
-        }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("cdr function unknown argument type");
-        }
-        match value_option.unwrap() {
-            &DataType::List(ref vec) => {
-                if vec.len() > 0 {
-                    Ok(Some(DataType::List((&vec[1..]).to_vec())))
-                } else {
-                    Err("cdr function requires a non-empty list")
-                }
-            },
-            &DataType::Pair(ref p) => Ok(Some(*(p.1).clone())),
-            _ => Err("cdr function requires an argument of type 'list'/ 'pair'")
+        check_arity("cdr", &vec, Arity::Exactly(1))?;
+        match vec[0] {
+            DataType::Pair(ref p) => Ok(Some(p.cdr())),
+            ref other => Err(SchemeError::wrong_type("cdr", "pair", other))
         }
     }))));
 
     map.insert("cons".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "cons", vec);
-        if vec.len() != 2 {
-            return Err("cons function requires two argument only");
+        check_arity("cons", &vec, Arity::Exactly(2))?;
+        let mut vec = vec.into_iter();
+        Ok(Some(DataType::cons(vec.next().unwrap(), vec.next().unwrap())))
+    }))));
+
+    map.insert("set-car!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "set-car!", vec);
+        check_arity("set-car!", &vec, Arity::Exactly(2))?;
+        match vec[0] {
+            DataType::Pair(ref p) => {
+                p.set_car(vec[1].clone());
+                Ok(None)
+            }
+            ref other => Err(SchemeError::wrong_type("set-car!", "pair", other))
         }
+    }))));
 
-        if let (Some(x), Some(y)) = (vec.get(0), vec.get(1)) {
-            match y {
-                &DataType::List(ref l) => {
-                    let mut result :Vec<DataType> = vec![(*x).clone()];
-                    result.extend(l.iter().map(|item| item.clone()));
-                    Ok(Some(DataType::List(result)))
-                },
-                _ => {
-                    Ok(Some(DataType::Pair(
-                        (Box::new(x.clone()), Box::new(y.clone()))
-                    )))
-                }
+    map.insert("set-cdr!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "set-cdr!", vec);
+        check_arity("set-cdr!", &vec, Arity::Exactly(2))?;
+        match vec[0] {
+            DataType::Pair(ref p) => {
+                p.set_cdr(vec[1].clone());
+                Ok(None)
             }
-        } else {
-            return Err("cons function unknown error")
+            ref other => Err(SchemeError::wrong_type("set-cdr!", "pair", other))
         }
     }))));
 
     map.insert("length".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "length", vec);
-        if vec.len() != 1 {
-            return Err("length function requires one argument only");
-        }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("length function unknown argument type");
-        }
-        match value_option.unwrap() {
-            &DataType::List(ref vec) => Ok(Some(DataType::Number(vec.len() as f64))),
-            _ => Err("length function requires an argument of type 'list'")
+        check_arity("length", &vec, Arity::Exactly(1))?;
+        match vec[0].list_items() {
+            Some(items) => Ok(Some(DataType::Number(Number::Integer(items.len() as i64)))),
+            None => Err(SchemeError::wrong_type("length", "list", &vec[0]))
         }
     }))));
 
     map.insert("list".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "list", vec);
-        Ok(Some(DataType::List(vec)))
+        Ok(Some(DataType::list(vec)))
     }))));
 
     map.insert("list?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "list?", vec);
-        if vec.len() != 1 {
-            return Err("list? function requires one argument only");
+        check_arity("list?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(vec[0].list_items().is_some())))
+    }))));
+
+    map.insert("map".to_string(), DataType::Primitive(Primitive::Map));
+    map.insert("for-each".to_string(), DataType::Primitive(Primitive::ForEach));
+
+    // caar through cddddr: the letters between `c` and `r` are applied from right to left
+    for len in 2..5 {
+        for bits in 0..1 << len {
+            let path = (0..len).map(|i| if bits & (1 << i) == 0 { 'a' } else { 'd' }).collect::<String>();
+            let name = format!("c{}r", path);
+            map.insert(name.clone(), DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+                debug!("Function - name: {:?} - Args: {:?}", name, vec);
+                check_arity(&name, &vec, Arity::Exactly(1))?;
+                path.chars().rev().try_fold(vec[0].clone(), |value, step| match value {
+                    DataType::Pair(ref p) => Ok(if step == 'a' { p.car() } else { p.cdr() }),
+                    ref other => Err(SchemeError::wrong_type(&name, "pair", other))
+                }).map(Some)
+            }))));
         }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("list? function unknown argument type");
+    }
+
+    map.insert("null?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "null?", vec);
+        check_arity("null?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::Null))))
+    }))));
+
+    map.insert("make-list".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "make-list", vec);
+        check_arity("make-list", &vec, Arity::Between(1, 2))?;
+        let k = index("make-list", &vec[0])?;
+        let fill = vec.get(1).cloned().unwrap_or(DataType::Unspecified);
+        Ok(Some(DataType::list(vec![fill; k])))
+    }))));
+
+    map.insert("list-tail".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "list-tail", vec);
+        check_arity("list-tail", &vec, Arity::Exactly(2))?;
+        let k = index("list-tail", &vec[1])?;
+        match list_tail(&vec[0], k) {
+            Some(tail) => Ok(Some(tail)),
+            None => Err(SchemeError::wrong_type("list-tail", "index within the list", &vec[1]))
         }
-        match value_option.unwrap() {
-            &DataType::List(_) => Ok(Some(DataType::Bool(true))),
-            _ => Ok(Some(DataType::Bool(false)))
+    }))));
+
+    map.insert("list-ref".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "list-ref", vec);
+        check_arity("list-ref", &vec, Arity::Exactly(2))?;
+        let k = index("list-ref", &vec[1])?;
+        match list_tail(&vec[0], k) {
+            Some(DataType::Pair(ref p)) => Ok(Some(p.car())),
+            _ => Err(SchemeError::wrong_type("list-ref", "index within the list", &vec[1]))
         }
     }))));
 
-    map.insert("map".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, env: Rc<RefCell<Env>>| {
-        debug!("Function - name: {:?} - Args: {:?}", "map", vec);
-        if vec.len() != 2 {
-            return Err("map function requires two argument only");
+    map.insert("list-copy".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "list-copy", vec);
+        check_arity("list-copy", &vec, Arity::Exactly(1))?;
+        if let Some(items) = vec[0].list_items() {
+            return Ok(Some(DataType::list(items)));
         }
+        // an improper list keeps its tail, and anything else is returned as it is
+        let mut items = vec![];
+        let mut rest = vec[0].clone();
+        while let DataType::Pair(pair) = rest {
+            items.push(pair.car());
+            rest = pair.cdr();
+        }
+        Ok(Some(DataType::list_with_tail(items, rest)))
+    }))));
 
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("map function unknown argument type");
+    map.insert("reverse".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "reverse", vec);
+        check_arity("reverse", &vec, Arity::Exactly(1))?;
+        match vec[0].list_items() {
+            Some(items) => Ok(Some(items.into_iter().fold(DataType::Null, |tail, item| DataType::cons(item, tail)))),
+            None => Err(SchemeError::wrong_type("reverse", "list", &vec[0]))
         }
+    }))));
 
-        if let (Some(d), Some(&DataType::List(ref l))) = (vec.first(), vec.get(1)) {
-            match d {
-                &DataType::Proc(ref f) => {
-                    let list = l.iter()
-                        .map(|item| f.call(vec![item.clone()], env.clone()))
-                        .flat_map(|x| x.ok())
-                        .filter(|x| x.is_some())
-                        .flat_map(|x| x)
-                        .collect::<Vec<DataType>>();
+    map.insert("memq".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "memq", vec);
+        check_arity("memq", &vec, Arity::Exactly(2))?;
+        member("memq", &vec[0], &vec[1], |a, b| Ok(eqv(a, b)))
+    }))));
 
-                    Ok(Some(DataType::List(list)))
-                },
-                &DataType::Lambda(ref p) => {
-                    let list = l.iter().map(|item| {
-                        let procedure_local = p.env.borrow_mut().local.clone();
-                        let args = vec![item.clone()];
-                        for (name_ref, value_ref) in p.params.iter().zip(args.into_iter()) {
-                            if let (Some(&DataType::Symbol(ref name)), Some(ref value)) = (Some(name_ref), Some(value_ref)) {
-                                procedure_local.borrow_mut().insert(name.to_string(), value.clone());
-                            } else {
-                                unreachable!()
-                            }
-                        }
+    map.insert("memv".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "memv", vec);
+        check_arity("memv", &vec, Arity::Exactly(2))?;
+        member("memv", &vec[0], &vec[1], |a, b| Ok(eqv(a, b)))
+    }))));
 
-                        let proc_env = Env {
-                            local: procedure_local,
-                            parent: p.env.borrow_mut().parent.clone()
-                        };
+    map.insert("member".to_string(), DataType::Primitive(Primitive::Member));
 
-                        debug!("proc_env: {:?}", proc_env);
-                        eval(Some(p.body.clone()), Rc::new(RefCell::new(proc_env)))
-                    }).flat_map(|x| x.ok())
-                        .filter(|x| x.is_some())
-                        .flat_map(|x| x)
-                        .collect::<Vec<DataType>>();
+    map.insert("assq".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "assq", vec);
+        check_arity("assq", &vec, Arity::Exactly(2))?;
+        assoc("assq", &vec[0], &vec[1], |a, b| Ok(eqv(a, b)))
+    }))));
 
-                    Ok(Some(DataType::List(list)))
-                },
-                _ => unreachable!()
-            }
-        } else {
-            Err("syntax error")
-        }
+    map.insert("assv".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "assv", vec);
+        check_arity("assv", &vec, Arity::Exactly(2))?;
+        assoc("assv", &vec[0], &vec[1], |a, b| Ok(eqv(a, b)))
     }))));
 
+    map.insert("assoc".to_string(), DataType::Primitive(Primitive::Assoc));
+
     map.insert("max".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "max", vec);
-        let is_numbers = vec.iter().all(|&ref x| if let &DataType::Number(_) = x { true } else { false });
-        if !is_numbers {
-            return Err("wrong argument datatype");
-        }
-        let numbers = vec.iter().filter_map(|&ref x| { if let &DataType::Number(ref y) = x { Some(y.clone()) } else { None } });
-        let data = numbers.map(|x| {
-            let y: f64 = x.clone().into();
-            y
-        }).float_max();
-        Ok(Some(DataType::Number(data.into())))
+        check_arity("max", &vec, Arity::AtLeast(1))?;
+        Ok(Some(DataType::Number(extremum(reals("max", &vec)?, Ordering::Greater))))
     }))));
 
     map.insert("min".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "min", vec);
-        let is_numbers = vec.iter().all(|&ref x| if let &DataType::Number(_) = x { true } else { false });
-        if !is_numbers {
-            return Err("wrong argument datatype");
-        }
-        let numbers = vec.iter().filter_map(|&ref x| { if let &DataType::Number(ref y) = x { Some(y.clone()) } else { None } });
+        check_arity("min", &vec, Arity::AtLeast(1))?;
+        Ok(Some(DataType::Number(extremum(reals("min", &vec)?, Ordering::Less))))
+    }))));
+
+    // eq? is eqv?: numbers and characters are always compared by value
+    map.insert("eq?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "eq?", vec);
+        check_arity("eq?", &vec, Arity::Exactly(2))?;
+        Ok(Some(DataType::Bool(eqv(&vec[0], &vec[1]))))
+    }))));
+
+    map.insert("eqv?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "eqv?", vec);
+        check_arity("eqv?", &vec, Arity::Exactly(2))?;
+        Ok(Some(DataType::Bool(eqv(&vec[0], &vec[1]))))
+    }))));
 
-        let data = numbers.map(|x| {
-            let y: f64 = x.clone().into();
-            y
-        }).float_min();
-        Ok(Some(DataType::Number(data.into())))
+    map.insert("equal?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "equal?", vec);
+        check_arity("equal?", &vec, Arity::Exactly(2))?;
+        Ok(Some(DataType::Bool(vec[0] == vec[1])))
     }))));
 
     map.insert("not".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "not", vec);
-        if vec.len() != 1 {
-            return Err("not function requires one argument only");
-        }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("not function unknown argument type");
-        }
-        match value_option.unwrap() {
-            &DataType::Bool(b) => Ok(Some(DataType::Bool(!b))),
-            _ => Err("not function requires an argument of type 'boolean'")
-        }
+        check_arity("not", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(!is_true(&vec[0]))))
     }))));
 
     map.insert("number?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "number?", vec);
-        if vec.len() != 1 {
-            return Err("number? function requires one argument only");
-        }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("number? function unknown argument type");
-        }
-        match value_option.unwrap() {
-            &DataType::Number(_) => Ok(Some(DataType::Bool(true))),
-            _ => Ok(Some(DataType::Bool(false)))
-        }
+        check_arity("number?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::Number(_)))))
     }))));
+
     map.insert("pair?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "pair?", vec);
-        if vec.len() != 1 {
-            return Err("pair? function requires one argument only");
-        }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("pair? function unknown argument type");
-        }
-        match value_option.unwrap() {
-            &DataType::Pair(_) => Ok(Some(DataType::Bool(true))),
-            _ => Ok(Some(DataType::Bool(false)))
-        }
+        check_arity("pair?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::Pair(_)))))
     }))));
 
     map.insert("print".to_string(), DataType::Proc(
         Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
             debug!("Function - name: {:?} - Args: {:?}", "print", vec);
-            if vec.len() != 1 {
-                return Err("print function requires one argument only");
-            }
-
-            let value_option = vec.first();
-            if value_option.is_none() {
-                return Err("unknown argument type");
-            }
-            println!("{}", datatype2str(value_option.unwrap()));
-            //        print_fn(value_option.unwrap());
+            check_arity("print", &vec, Arity::Exactly(1))?;
+            println!("{}", datatype2str(&vec[0]));
             Ok(None)
         }))));
 
     map.insert("procedure?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
         debug!("Function - name: {:?} - Args: {:?}", "procedure?", vec);
-        if vec.len() != 1 {
-            return Err("procedure? function requires one argument only");
+        check_arity("procedure?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(vec[0].is_procedure())))
+    }))));
+
+    map.insert("string?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string?", vec);
+        check_arity("string?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::String(_)))))
+    }))));
+
+    map.insert("char?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "char?", vec);
+        check_arity("char?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::Char(_)))))
+    }))));
+
+    map.insert("char->integer".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "char->integer", vec);
+        check_arity("char->integer", &vec, Arity::Exactly(1))?;
+        let c = chars("char->integer", &vec)?[0];
+        Ok(Some(DataType::Number(Number::Integer(c as i64))))
+    }))));
+
+    map.insert("integer->char".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "integer->char", vec);
+        check_arity("integer->char", &vec, Arity::Exactly(1))?;
+        match vec[0] {
+            DataType::Number(Number::Integer(n)) if n >= 0 && n <= u32::MAX as i64 => match ::std::char::from_u32(n as u32) {
+                Some(c) => Ok(Some(DataType::Char(c))),
+                None => Err(SchemeError::wrong_type("integer->char", "Unicode scalar value", &vec[0]))
+            },
+            ref other => Err(SchemeError::wrong_type("integer->char", "Unicode scalar value", other))
         }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("procedure? function unknown argument type");
+    }))));
+
+    map.insert("char-upcase".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "char-upcase", vec);
+        check_arity("char-upcase", &vec, Arity::Exactly(1))?;
+        let c = chars("char-upcase", &vec)?[0];
+        Ok(Some(DataType::Char(convert_case(c, c.to_uppercase()))))
+    }))));
+
+    map.insert("char-downcase".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "char-downcase", vec);
+        check_arity("char-downcase", &vec, Arity::Exactly(1))?;
+        let c = chars("char-downcase", &vec)?[0];
+        Ok(Some(DataType::Char(convert_case(c, c.to_lowercase()))))
+    }))));
+
+    let classes = [
+        ("char-alphabetic?", char::is_alphabetic as fn(char) -> bool),
+        ("char-numeric?", |c| digit_value(c).is_some()),
+        ("char-whitespace?", char::is_whitespace)
+    ];
+    for &(name, class) in classes.iter() {
+        map.insert(name.to_string(), DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+            debug!("Function - name: {:?} - Args: {:?}", name, vec);
+            check_arity(name, &vec, Arity::Exactly(1))?;
+            Ok(Some(DataType::Bool(class(chars(name, &vec)?[0]))))
+        }))));
+    }
+
+    map.insert("digit-value".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "digit-value", vec);
+        check_arity("digit-value", &vec, Arity::Exactly(1))?;
+        Ok(Some(match digit_value(chars("digit-value", &vec)?[0]) {
+            Some(d) => DataType::Number(Number::Integer(d as i64)),
+            None => DataType::Bool(false)
+        }))
+    }))));
+
+    // each of these holds when every character is related in that way to the next one
+    let orders = [
+        ("char=?", (|o| o == Ordering::Equal) as fn(Ordering) -> bool),
+        ("char<?", |o| o == Ordering::Less),
+        ("char>?", |o| o == Ordering::Greater),
+        ("char<=?", |o| o != Ordering::Greater),
+        ("char>=?", |o| o != Ordering::Less)
+    ];
+    for &(name, order) in orders.iter() {
+        map.insert(name.to_string(), DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+            debug!("Function - name: {:?} - Args: {:?}", name, vec);
+            check_arity(name, &vec, Arity::AtLeast(2))?;
+            let chars = chars(name, &vec)?;
+            Ok(Some(DataType::Bool(chars.windows(2).all(|w| order(w[0].cmp(&w[1]))))))
+        }))));
+    }
+
+    map.insert("make-string".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "make-string", vec);
+        check_arity("make-string", &vec, Arity::Between(1, 2))?;
+        let k = index("make-string", &vec[0])?;
+        let fill = match vec.get(1) {
+            Some(_) => chars("make-string", &vec[1..])?[0],
+            None => ' '
+        };
+        Ok(Some(DataType::string(std::iter::repeat_n(fill, k).collect::<String>())))
+    }))));
+
+    map.insert("string".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string", vec);
+        Ok(Some(DataType::string(chars("string", &vec)?.into_iter().collect::<String>())))
+    }))));
+
+    map.insert("string-length".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string-length", vec);
+        check_arity("string-length", &vec, Arity::Exactly(1))?;
+        let s = strings("string-length", &vec)?.remove(0);
+        Ok(Some(DataType::Number(Number::Integer(s.len() as i64))))
+    }))));
+
+    map.insert("string-ref".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string-ref", vec);
+        check_arity("string-ref", &vec, Arity::Exactly(2))?;
+        let s = strings("string-ref", &vec[..1])?.remove(0);
+        match s.get(index("string-ref", &vec[1])?) {
+            Some(c) => Ok(Some(DataType::Char(c))),
+            None => Err(SchemeError::wrong_type("string-ref", "index within the string", &vec[1]))
         }
-        match value_option.unwrap() {
-            &DataType::Proc(_) => Ok(Some(DataType::Bool(true))),
-            &DataType::Lambda(_) => Ok(Some(DataType::Bool(true))),
-            _ => Ok(Some(DataType::Bool(false)))
+    }))));
+
+    map.insert("string-set!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string-set!", vec);
+        check_arity("string-set!", &vec, Arity::Exactly(3))?;
+        let s = strings("string-set!", &vec[..1])?.remove(0);
+        let k = index("string-set!", &vec[1])?;
+        let c = chars("string-set!", &vec[2..])?[0];
+        if !s.set(k, c) {
+            return Err(SchemeError::wrong_type("string-set!", "index within the string", &vec[1]));
         }
+        Ok(None)
     }))));
 
-    map.insert("string?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
-        debug!("Function - name: {:?} - Args: {:?}", "string?", vec);
-        if vec.len() != 1 {
-            return Err("string? function requires one argument only");
+    map.insert("string-fill!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string-fill!", vec);
+        check_arity("string-fill!", &vec, Arity::Between(2, 4))?;
+        let s = strings("string-fill!", &vec[..1])?.remove(0);
+        let c = chars("string-fill!", &vec[1..2])?[0];
+        let (start, end) = range("string-fill!", &vec, 2, s.len(), "index within the string")?;
+        s.fill(c, start, end);
+        Ok(None)
+    }))));
+
+    map.insert("substring".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "substring", vec);
+        check_arity("substring", &vec, Arity::Exactly(3))?;
+        let s = strings("substring", &vec[..1])?.remove(0);
+        let (start, end) = range("substring", &vec, 1, s.len(), "index within the string")?;
+        Ok(Some(DataType::string(substring(&s.to_string(), start, end))))
+    }))));
+
+    map.insert("string-copy".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string-copy", vec);
+        check_arity("string-copy", &vec, Arity::Between(1, 3))?;
+        let s = strings("string-copy", &vec[..1])?.remove(0);
+        let (start, end) = range("string-copy", &vec, 1, s.len(), "index within the string")?;
+        Ok(Some(DataType::string(substring(&s.to_string(), start, end))))
+    }))));
+
+    map.insert("string-append".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string-append", vec);
+        let appended = strings("string-append", &vec)?.iter()
+            .map(|s| s.to_string())
+            .collect::<String>();
+        Ok(Some(DataType::string(appended)))
+    }))));
+
+    map.insert("string->list".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string->list", vec);
+        check_arity("string->list", &vec, Arity::Between(1, 3))?;
+        let s = strings("string->list", &vec[..1])?.remove(0);
+        let (start, end) = range("string->list", &vec, 1, s.len(), "index within the string")?;
+        let chars = s.to_string().chars().skip(start).take(end - start).map(DataType::Char).collect();
+        Ok(Some(DataType::list(chars)))
+    }))));
+
+    map.insert("list->string".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "list->string", vec);
+        check_arity("list->string", &vec, Arity::Exactly(1))?;
+        match vec[0].list_items() {
+            Some(items) => Ok(Some(DataType::string(chars("list->string", &items)?.into_iter().collect::<String>()))),
+            None => Err(SchemeError::wrong_type("list->string", "list", &vec[0]))
+        }
+    }))));
+
+    map.insert("string->number".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string->number", vec);
+        check_arity("string->number", &vec, Arity::Between(1, 2))?;
+        let s = strings("string->number", &vec[..1])?.remove(0);
+        let radix = match vec.get(1) {
+            Some(&DataType::Number(Number::Integer(r))) if [2, 8, 10, 16].contains(&r) => r as u32,
+            Some(other) => return Err(SchemeError::wrong_type("string->number", "radix of 2, 8, 10 or 16", other)),
+            None => 10
+        };
+        Ok(Some(Number::parse_with_radix(&s.to_string(), radix).map_or(DataType::Bool(false), DataType::Number)))
+    }))));
+
+    map.insert("number->string".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "number->string", vec);
+        check_arity("number->string", &vec, Arity::Between(1, 2))?;
+        let n = &numbers("number->string", &vec[..1])?[0];
+        let radix = match vec.get(1) {
+            Some(&DataType::Number(Number::Integer(r))) if [2, 8, 10, 16].contains(&r) => r as u32,
+            Some(other) => return Err(SchemeError::wrong_type("number->string", "radix of 2, 8, 10 or 16", other)),
+            None => 10
+        };
+        match n.to_string_radix(radix) {
+            Some(s) => Ok(Some(DataType::string(s))),
+            None => Err(SchemeError::wrong_type("number->string", "exact number", &vec[0]))
         }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("string? function unknown argument type");
+    }))));
+
+    map.insert("string->symbol".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string->symbol", vec);
+        check_arity("string->symbol", &vec, Arity::Exactly(1))?;
+        let s = strings("string->symbol", &vec)?.remove(0);
+        Ok(Some(DataType::Symbol(s.to_string())))
+    }))));
+
+    map.insert("symbol->string".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "symbol->string", vec);
+        check_arity("symbol->string", &vec, Arity::Exactly(1))?;
+        match vec[0] {
+            DataType::Symbol(ref s) => Ok(Some(DataType::string(s.clone()))),
+            ref other => Err(SchemeError::wrong_type("symbol->string", "symbol", other))
         }
-        match value_option.unwrap() {
-            &DataType::String(_) => Ok(Some(DataType::Bool(true))),
-            _ => Ok(Some(DataType::Bool(false)))
+    }))));
+
+    let conversions = [
+        ("string-upcase", str::to_uppercase as fn(&str) -> String),
+        ("string-downcase", str::to_lowercase),
+        ("string-foldcase", foldcase)
+    ];
+    for &(name, convert) in conversions.iter() {
+        map.insert(name.to_string(), DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+            debug!("Function - name: {:?} - Args: {:?}", name, vec);
+            check_arity(name, &vec, Arity::Exactly(1))?;
+            let s = strings(name, &vec)?.remove(0);
+            Ok(Some(DataType::string(convert(&s.to_string()))))
+        }))));
+    }
+
+    // like the char comparisons, the -ci ones comparing the case folded strings
+    let orders = [
+        ("string=?", (|o| o == Ordering::Equal) as fn(Ordering) -> bool),
+        ("string<?", |o| o == Ordering::Less),
+        ("string>?", |o| o == Ordering::Greater),
+        ("string<=?", |o| o != Ordering::Greater),
+        ("string>=?", |o| o != Ordering::Less)
+    ];
+    for &(name, order) in orders.iter() {
+        for &fold in [false, true].iter() {
+            let name = if fold { name.replacen("string", "string-ci", 1) } else { name.to_string() };
+            map.insert(name.clone(), DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+                debug!("Function - name: {:?} - Args: {:?}", name, vec);
+                check_arity(&name, &vec, Arity::AtLeast(2))?;
+                let strings = strings(&name, &vec)?.iter()
+                    .map(|s| if fold { foldcase(&s.to_string()) } else { s.to_string() })
+                    .collect::<Vec<_>>();
+                Ok(Some(DataType::Bool(strings.windows(2).all(|w| order(w[0].cmp(&w[1]))))))
+            }))));
         }
+    }
+
+    map.insert("vector?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector?", vec);
+        check_arity("vector?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::Vector(_)))))
     }))));
 
-    map.insert("symbol?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
-        debug!("Function - name: {:?} - Args: {:?}", "symbol?", vec);
-        if vec.len() != 1 {
-            return Err("symbol? function requires one argument only");
+    map.insert("make-vector".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "make-vector", vec);
+        check_arity("make-vector", &vec, Arity::Between(1, 2))?;
+        let k = index("make-vector", &vec[0])?;
+        let fill = vec.get(1).cloned().unwrap_or(DataType::Unspecified);
+        Ok(Some(DataType::Vector(Vector::new(vec![fill; k]))))
+    }))));
+
+    map.insert("vector".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector", vec);
+        Ok(Some(DataType::Vector(Vector::new(vec))))
+    }))));
+
+    map.insert("vector-length".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector-length", vec);
+        check_arity("vector-length", &vec, Arity::Exactly(1))?;
+        let v = vectors("vector-length", &vec)?.remove(0);
+        Ok(Some(DataType::Number(Number::Integer(v.len() as i64))))
+    }))));
+
+    map.insert("vector-ref".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector-ref", vec);
+        check_arity("vector-ref", &vec, Arity::Exactly(2))?;
+        let v = vectors("vector-ref", &vec[..1])?.remove(0);
+        let item = v.borrow().get(index("vector-ref", &vec[1])?).cloned();
+        match item {
+            Some(item) => Ok(Some(item)),
+            None => Err(SchemeError::wrong_type("vector-ref", "index within the vector", &vec[1]))
+        }
+    }))));
+
+    map.insert("vector-set!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector-set!", vec);
+        check_arity("vector-set!", &vec, Arity::Exactly(3))?;
+        let v = vectors("vector-set!", &vec[..1])?.remove(0);
+        let k = index("vector-set!", &vec[1])?;
+        match v.borrow_mut().get_mut(k) {
+            Some(item) => *item = vec[2].clone(),
+            None => return Err(SchemeError::wrong_type("vector-set!", "index within the vector", &vec[1]))
+        }
+        Ok(None)
+    }))));
+
+    map.insert("vector->list".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector->list", vec);
+        check_arity("vector->list", &vec, Arity::Between(1, 3))?;
+        let v = vectors("vector->list", &vec[..1])?.remove(0);
+        let (start, end) = range("vector->list", &vec, 1, v.len(), "index within the vector")?;
+        let items = v.borrow()[start..end].to_vec();
+        Ok(Some(DataType::list(items)))
+    }))));
+
+    map.insert("list->vector".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "list->vector", vec);
+        check_arity("list->vector", &vec, Arity::Exactly(1))?;
+        match vec[0].list_items() {
+            Some(items) => Ok(Some(DataType::Vector(Vector::new(items)))),
+            None => Err(SchemeError::wrong_type("list->vector", "list", &vec[0]))
+        }
+    }))));
+
+    map.insert("vector-fill!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector-fill!", vec);
+        check_arity("vector-fill!", &vec, Arity::Between(2, 4))?;
+        let v = vectors("vector-fill!", &vec[..1])?.remove(0);
+        let (start, end) = range("vector-fill!", &vec, 2, v.len(), "index within the vector")?;
+        for item in &mut v.borrow_mut()[start..end] {
+            *item = vec[1].clone();
+        }
+        Ok(None)
+    }))));
+
+    map.insert("vector-copy".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector-copy", vec);
+        check_arity("vector-copy", &vec, Arity::Between(1, 3))?;
+        let v = vectors("vector-copy", &vec[..1])?.remove(0);
+        let (start, end) = range("vector-copy", &vec, 1, v.len(), "index within the vector")?;
+        let items = v.borrow()[start..end].to_vec();
+        Ok(Some(DataType::Vector(Vector::new(items))))
+    }))));
+
+    map.insert("vector-copy!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector-copy!", vec);
+        check_arity("vector-copy!", &vec, Arity::Between(3, 5))?;
+        let to = vectors("vector-copy!", &vec[..1])?.remove(0);
+        let at = index("vector-copy!", &vec[1])?;
+        let from = vectors("vector-copy!", &vec[2..3])?.remove(0);
+        let (start, end) = range("vector-copy!", &vec, 3, from.len(), "index within the vector")?;
+        if at + (end - start) > to.len() {
+            return Err(SchemeError::wrong_type("vector-copy!", "index within the vector", &vec[1]));
+        }
+        to.copy_from(at, &from, start, end);
+        Ok(None)
+    }))));
+
+    map.insert("vector-append".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "vector-append", vec);
+        let items = vectors("vector-append", &vec)?.iter().flat_map(Vector::items).collect();
+        Ok(Some(DataType::Vector(Vector::new(items))))
+    }))));
+
+    map.insert("vector-map".to_string(), DataType::Primitive(Primitive::VectorMap));
+    map.insert("vector-for-each".to_string(), DataType::Primitive(Primitive::VectorForEach));
+
+    map.insert("bytevector?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "bytevector?", vec);
+        check_arity("bytevector?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::Bytevector(_)))))
+    }))));
+
+    map.insert("make-bytevector".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "make-bytevector", vec);
+        check_arity("make-bytevector", &vec, Arity::Between(1, 2))?;
+        let k = index("make-bytevector", &vec[0])?;
+        let fill = match vec.get(1) {
+            Some(fill) => byte("make-bytevector", fill)?,
+            None => 0
+        };
+        Ok(Some(DataType::Bytevector(Bytevector::new(vec![fill; k]))))
+    }))));
+
+    map.insert("bytevector".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "bytevector", vec);
+        let bytes = vec.iter().map(|b| byte("bytevector", b)).collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(DataType::Bytevector(Bytevector::new(bytes))))
+    }))));
+
+    map.insert("bytevector-length".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "bytevector-length", vec);
+        check_arity("bytevector-length", &vec, Arity::Exactly(1))?;
+        let b = bytevectors("bytevector-length", &vec)?.remove(0);
+        Ok(Some(DataType::Number(Number::Integer(b.len() as i64))))
+    }))));
+
+    map.insert("bytevector-u8-ref".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "bytevector-u8-ref", vec);
+        check_arity("bytevector-u8-ref", &vec, Arity::Exactly(2))?;
+        let b = bytevectors("bytevector-u8-ref", &vec[..1])?.remove(0);
+        let byte = b.borrow().get(index("bytevector-u8-ref", &vec[1])?).cloned();
+        match byte {
+            Some(byte) => Ok(Some(DataType::Number(Number::Integer(i64::from(byte))))),
+            None => Err(SchemeError::wrong_type("bytevector-u8-ref", "index within the bytevector", &vec[1]))
+        }
+    }))));
+
+    map.insert("bytevector-u8-set!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "bytevector-u8-set!", vec);
+        check_arity("bytevector-u8-set!", &vec, Arity::Exactly(3))?;
+        let b = bytevectors("bytevector-u8-set!", &vec[..1])?.remove(0);
+        let k = index("bytevector-u8-set!", &vec[1])?;
+        let value = byte("bytevector-u8-set!", &vec[2])?;
+        match b.borrow_mut().get_mut(k) {
+            Some(byte) => *byte = value,
+            None => return Err(SchemeError::wrong_type("bytevector-u8-set!", "index within the bytevector", &vec[1]))
+        }
+        Ok(None)
+    }))));
+
+    map.insert("bytevector-copy".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "bytevector-copy", vec);
+        check_arity("bytevector-copy", &vec, Arity::Between(1, 3))?;
+        let b = bytevectors("bytevector-copy", &vec[..1])?.remove(0);
+        let (start, end) = range("bytevector-copy", &vec, 1, b.len(), "index within the bytevector")?;
+        let bytes = b.borrow()[start..end].to_vec();
+        Ok(Some(DataType::Bytevector(Bytevector::new(bytes))))
+    }))));
+
+    map.insert("bytevector-copy!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "bytevector-copy!", vec);
+        check_arity("bytevector-copy!", &vec, Arity::Between(3, 5))?;
+        let to = bytevectors("bytevector-copy!", &vec[..1])?.remove(0);
+        let at = index("bytevector-copy!", &vec[1])?;
+        let from = bytevectors("bytevector-copy!", &vec[2..3])?.remove(0);
+        let (start, end) = range("bytevector-copy!", &vec, 3, from.len(), "index within the bytevector")?;
+        if at + (end - start) > to.len() {
+            return Err(SchemeError::wrong_type("bytevector-copy!", "index within the bytevector", &vec[1]));
         }
-        let value_option = vec.first();
-        if value_option.is_none() {
-            return Err("symbol? function unknown argument type");
+        to.copy_from(at, &from, start, end);
+        Ok(None)
+    }))));
+
+    map.insert("bytevector-append".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "bytevector-append", vec);
+        let bytes = bytevectors("bytevector-append", &vec)?.iter().flat_map(Bytevector::items).collect();
+        Ok(Some(DataType::Bytevector(Bytevector::new(bytes))))
+    }))));
+
+    map.insert("utf8->string".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "utf8->string", vec);
+        check_arity("utf8->string", &vec, Arity::Between(1, 3))?;
+        let b = bytevectors("utf8->string", &vec[..1])?.remove(0);
+        let (start, end) = range("utf8->string", &vec, 1, b.len(), "index within the bytevector")?;
+        let bytes = b.borrow()[start..end].to_vec();
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(Some(DataType::string(s))),
+            Err(_) => Err(SchemeError::wrong_type("utf8->string", "UTF-8 encoded bytevector", &vec[0]))
         }
-        match value_option.unwrap() {
-            &DataType::Symbol(_) => Ok(Some(DataType::Bool(true))),
-            _ => Ok(Some(DataType::Bool(false)))
+    }))));
+
+    map.insert("string->utf8".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "string->utf8", vec);
+        check_arity("string->utf8", &vec, Arity::Between(1, 3))?;
+        let s = strings("string->utf8", &vec[..1])?.remove(0);
+        let (start, end) = range("string->utf8", &vec, 1, s.len(), "index within the string")?;
+        let bytes = substring(&s.to_string(), start, end).into_bytes();
+        Ok(Some(DataType::Bytevector(Bytevector::new(bytes))))
+    }))));
+
+    map.insert("native-endianness".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "native-endianness", vec);
+        check_arity("native-endianness", &vec, Arity::Exactly(0))?;
+        let endianness = if cfg!(target_endian = "big") { "big" } else { "little" };
+        Ok(Some(DataType::Symbol(endianness.to_string())))
+    }))));
+
+    // bytevector-u16-ref through bytevector-s64-set!, which read and write
+    // `size`-byte integers at any index, in the byte order given by their last argument
+    for &size in [2usize, 4, 8].iter() {
+        for &signed in [false, true].iter() {
+            let bits = size * 8;
+            let (min, max) = if signed {
+                (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+            } else {
+                (0, (1i128 << bits) - 1)
+            };
+            let kind = format!("{}{}", if signed { 's' } else { 'u' }, bits);
+
+            let name = format!("bytevector-{}-ref", kind);
+            map.insert(name.clone(), DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+                debug!("Function - name: {:?} - Args: {:?}", name, vec);
+                check_arity(&name, &vec, Arity::Exactly(3))?;
+                let b = bytevectors(&name, &vec[..1])?.remove(0);
+                let k = index(&name, &vec[1])?;
+                let big = big_endian(&name, &vec[2])?;
+                if k + size > b.len() {
+                    return Err(SchemeError::wrong_type(&name, "index within the bytevector", &vec[1]));
+                }
+                let mut bytes = b.borrow()[k..k + size].to_vec();
+                if !big {
+                    bytes.reverse();
+                }
+                let value = bytes.iter().fold(0i128, |value, &byte| value << 8 | i128::from(byte));
+                let value = if value > max { value - (1i128 << bits) } else { value };
+                Ok(Some(DataType::Number(Number::from(value))))
+            }))));
+
+            let name = format!("bytevector-{}-set!", kind);
+            map.insert(name.clone(), DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+                debug!("Function - name: {:?} - Args: {:?}", name, vec);
+                check_arity(&name, &vec, Arity::Exactly(4))?;
+                let b = bytevectors(&name, &vec[..1])?.remove(0);
+                let k = index(&name, &vec[1])?;
+                let value = match vec[2] {
+                    DataType::Number(ref n) => n.to_i128().filter(|&n| n >= min && n <= max),
+                    _ => None
+                };
+                let value = value.ok_or_else(|| SchemeError::wrong_type(&name, "exact integer in range", &vec[2]))?;
+                let big = big_endian(&name, &vec[3])?;
+                if k + size > b.len() {
+                    return Err(SchemeError::wrong_type(&name, "index within the bytevector", &vec[1]));
+                }
+                let mut bytes = (0..size).rev().map(|i| (value >> (8 * i)) as u8).collect::<Vec<u8>>();
+                if !big {
+                    bytes.reverse();
+                }
+                b.borrow_mut()[k..k + size].copy_from_slice(&bytes);
+                Ok(None)
+            }))));
         }
+    }
+
+    map.insert("symbol?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "symbol?", vec);
+        check_arity("symbol?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::Symbol(_)))))
+    }))));
+
+    // the procedures a hash table can compare its keys with, which must have
+    // been defined by now
+    let equivalences = [("eq?", Equivalence::Eq), ("eqv?", Equivalence::Eqv),
+                        ("equal?", Equivalence::Equal), ("string=?", Equivalence::String)].iter()
+        .map(|&(name, equivalence)| (map[name].clone(), equivalence))
+        .collect::<Vec<_>>();
+    for &weak in [false, true].iter() {
+        let name = if weak { "make-weak-hash-table" } else { "make-hash-table" };
+        let equivalences = equivalences.clone();
+        map.insert(name.to_string(), DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+            debug!("Function - name: {:?} - Args: {:?}", name, vec);
+            // a hash function may be given for compatibility with SRFI 69, but
+            // keys are always hashed consistently with the comparator
+            check_arity(name, &vec, Arity::Between(0, 2))?;
+            let equivalence = match vec.first() {
+                Some(compare) => match equivalences.iter().find(|&(p, _)| p == compare) {
+                    Some(&(_, equivalence)) => equivalence,
+                    None => return Err(SchemeError::wrong_type(name, "eq?, eqv?, equal? or string=?", compare))
+                },
+                None => Equivalence::Equal
+            };
+            if let Some(hash) = vec.get(1) {
+                if !hash.is_procedure() {
+                    return Err(SchemeError::wrong_type(name, "procedure", hash));
+                }
+            }
+            Ok(Some(DataType::HashTable(HashTable::new(equivalence, weak))))
+        }))));
+    }
+
+    map.insert("hash-table?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "hash-table?", vec);
+        check_arity("hash-table?", &vec, Arity::Exactly(1))?;
+        Ok(Some(DataType::Bool(matches!(vec[0], DataType::HashTable(_)))))
+    }))));
+
+    map.insert("hash-table-set!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "hash-table-set!", vec);
+        check_arity("hash-table-set!", &vec, Arity::Exactly(3))?;
+        let table = hash_tables("hash-table-set!", &vec[..1])?.remove(0);
+        table.set("hash-table-set!", vec[1].clone(), vec[2].clone())?;
+        Ok(None)
+    }))));
+
+    map.insert("hash-table-ref".to_string(), DataType::Primitive(Primitive::HashTableRef));
+
+    map.insert("hash-table-ref/default".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "hash-table-ref/default", vec);
+        check_arity("hash-table-ref/default", &vec, Arity::Exactly(3))?;
+        let table = hash_tables("hash-table-ref/default", &vec[..1])?.remove(0);
+        Ok(Some(table.get("hash-table-ref/default", &vec[1])?.unwrap_or_else(|| vec[2].clone())))
+    }))));
+
+    map.insert("hash-table-contains?".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "hash-table-contains?", vec);
+        check_arity("hash-table-contains?", &vec, Arity::Exactly(2))?;
+        let table = hash_tables("hash-table-contains?", &vec[..1])?.remove(0);
+        Ok(Some(DataType::Bool(table.get("hash-table-contains?", &vec[1])?.is_some())))
+    }))));
+
+    map.insert("hash-table-delete!".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "hash-table-delete!", vec);
+        check_arity("hash-table-delete!", &vec, Arity::Exactly(2))?;
+        let table = hash_tables("hash-table-delete!", &vec[..1])?.remove(0);
+        table.delete("hash-table-delete!", &vec[1])?;
+        Ok(None)
+    }))));
+
+    map.insert("hash-table-update!".to_string(), DataType::Primitive(Primitive::HashTableUpdate));
+    map.insert("hash-table-update!/default".to_string(), DataType::Primitive(Primitive::HashTableUpdateDefault));
+
+    let count = DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "hash-table-count", vec);
+        check_arity("hash-table-count", &vec, Arity::Exactly(1))?;
+        let table = hash_tables("hash-table-count", &vec)?.remove(0);
+        Ok(Some(DataType::Number(Number::Integer(table.len() as i64))))
+    })));
+    map.insert("hash-table-count".to_string(), count.clone());
+    map.insert("hash-table-size".to_string(), count);
+
+    map.insert("hash-table-keys".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "hash-table-keys", vec);
+        check_arity("hash-table-keys", &vec, Arity::Exactly(1))?;
+        let table = hash_tables("hash-table-keys", &vec)?.remove(0);
+        Ok(Some(DataType::list(table.entries().into_iter().map(|(key, _)| key).collect())))
+    }))));
+
+    map.insert("hash-table-values".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "hash-table-values", vec);
+        check_arity("hash-table-values", &vec, Arity::Exactly(1))?;
+        let table = hash_tables("hash-table-values", &vec)?.remove(0);
+        Ok(Some(DataType::list(table.entries().into_iter().map(|(_, value)| value).collect())))
     }))));
 
-    //    debug!("map start");
-    //    for (i, key) in map.keys().enumerate() {
-    //        debug!("{} => {}", i + 1, key);
-    //        match map.get(key) {
-    //            Some(&DataType::Proc(ref f)) => {
-    //                match f.call(vec![DataType::Integer(1), DataType::Integer(2), DataType::Float(5.1)]) {
-    //                    Ok(result) => { debug!("Execution is good. Result: {:?}", result); }
-    //                    Err(_) => { debug!("Execution is failed"); }
-    //                }
-    //            }
-    //            Some(&ref o) => {
-    //                debug!("{:?}", o);
-    //            },
-    //            None => {}
-    //        }
-    //    }
-    //    debug!("map end");
+    map.insert("hash-table->alist".to_string(), DataType::Proc(Function(Rc::new(|vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "hash-table->alist", vec);
+        check_arity("hash-table->alist", &vec, Arity::Exactly(1))?;
+        let table = hash_tables("hash-table->alist", &vec)?.remove(0);
+        Ok(Some(DataType::list(table.entries().into_iter().map(|(key, value)| DataType::cons(key, value)).collect())))
+    }))));
 
-    return map;
+    map.insert("hash-table-walk".to_string(), DataType::Primitive(Primitive::HashTableWalk));
+    map
 }
 
 fn datatype2str(value: &DataType) -> String {
-    match value {
-        &DataType::Bool(b) => format!("{}", b),
-        &DataType::Pair(ref p) => format!("({:?} . {:?})", p.0, p.1),
-        &DataType::Number(f) => format!("{}", f),
-        &DataType::Symbol(ref s) => format!("'{}", s),
-        &DataType::String(ref s) => format!("\"{}\"", s),
-        &DataType::Proc(ref p) => format!("{:?}", p),
-        &DataType::Lambda(ref p) => format!("{:?}", p),
-        &DataType::List(ref v) => format!("'({})", v.iter()
-            .map(|d| datatype2str(d)).collect::<Vec<_>>().join(" "))
-    }
-}
-
-fn ast2datatype(value: &AST) -> Result<DataType, &'static str> {
-    match value {
-        &AST::Children(ref v) => {
-            let children_result: Result<Vec<_>, _> = v.iter().map(|ast| ast2datatype(&ast)).collect();
-            if let Result::Err(ref e) = children_result { return Err(e); }
-
-            let children = children_result.unwrap().into_iter().collect::<Vec<DataType>>();
-            Ok(DataType::List(children))
-        }
-        &AST::Symbol(ref s) => {
-            if s.starts_with("#") {
-                if s.len() != 2 {
-                    return Err("syntax error");
+    match *value {
+        DataType::Bool(b) => format!("{}", b),
+        DataType::Pair(_) | DataType::Null => format!("'{}", external(value)),
+        DataType::Number(ref n) => format!("{}", n),
+        DataType::Symbol(ref s) => format!("'{}", s),
+        DataType::String(ref s) => format!("\"{}\"", escape_string(&s.borrow())),
+        DataType::Char(c) => write_char(c),
+        DataType::Vector(_) => external(value),
+        DataType::Bytevector(ref b) => format!("{:?}", b),
+        DataType::HashTable(ref t) => format!("{:?}", t),
+        DataType::Proc(ref p) => format!("{:?}", p),
+        DataType::Primitive(ref p) => format!("{:?}", p),
+        DataType::Continuation(ref k) => format!("{:?}", k),
+        DataType::Macro(ref m) => format!("{:?}", m),
+        DataType::Values(ref v) => v.iter().map(datatype2str).collect::<Vec<_>>().join(" "),
+        DataType::Unspecified => "#<unspecified>".to_string(),
+        DataType::Lambda(ref p) => format!("{:?}", p),
+        DataType::CaseLambda(ref p) => format!("{:?}", p)
+    }
+}
+
+/// Writes a datum inside a quoted list or a vector: symbols go bare and nested
+/// lists are written in list notation, with a dot before an improper tail.
+fn external(value: &DataType) -> String {
+    write_shared(value, &|leaf| match *leaf {
+        DataType::Symbol(ref s) => s.clone(),
+        DataType::Null => "()".to_string(),
+        ref other => datatype2str(other)
+    })
+}
+
+/// Writes the pairs and vectors in `value` in list and `#(...)` notation, and
+/// anything else with `leaf`. A pair or vector that contains itself is
+/// labelled where it is first written and referred to by its label after
+/// that, as in `#0=(1 2 . #0#)`, so circular structures can be written.
+fn write_shared(value: &DataType, leaf: &dyn Fn(&DataType) -> String) -> String {
+    let mut writer = Writer { cyclic: cyclic(value), labels: HashMap::new(), leaf };
+    writer.write(value)
+}
+
+/// The addresses of the pairs and vectors in `value` that can be reached again
+/// from inside themselves. Every cycle goes through at least one of them.
+fn cyclic(value: &DataType) -> HashSet<usize> {
+    // a depth-first walk, which finds a cycle whenever it comes back to a pair
+    // or vector on the path it is exploring
+    enum Step {
+        Enter(DataType),
+        Leave(usize)
+    }
+    let (mut path, mut done, mut cyclic) = (HashSet::new(), HashSet::new(), HashSet::new());
+    let mut steps = vec![Step::Enter(value.clone())];
+    while let Some(step) = steps.pop() {
+        let value = match step {
+            Step::Enter(value) => value,
+            Step::Leave(address) => {
+                path.remove(&address);
+                done.insert(address);
+                continue;
+            }
+        };
+        let address = match address(&value) {
+            Some(address) => address,
+            None => continue
+        };
+        if path.contains(&address) {
+            cyclic.insert(address);
+            continue;
+        }
+        if !done.contains(&address) {
+            path.insert(address);
+            steps.push(Step::Leave(address));
+            match value {
+                DataType::Pair(ref p) => {
+                    steps.push(Step::Enter(p.cdr()));
+                    steps.push(Step::Enter(p.car()));
+                }
+                DataType::Vector(ref v) => steps.extend(v.items().into_iter().rev().map(Step::Enter)),
+                _ => unreachable!()
+            }
+        }
+    }
+    cyclic
+}
+
+struct Writer<'a> {
+    cyclic: HashSet<usize>,
+    /// The labels given so far, by address.
+    labels: HashMap<usize, usize>,
+    leaf: &'a dyn Fn(&DataType) -> String
+}
+
+impl<'a> Writer<'a> {
+    fn write(&mut self, value: &DataType) -> String {
+        match address(value) {
+            Some(address) if self.cyclic.contains(&address) => {
+                if let Some(label) = self.labels.get(&address) {
+                    return format!("#{}#", label);
                 }
-                let c_option = s.chars().nth(1);
-                if let Some('t') = c_option {
-                    Ok(DataType::Bool(true))
-                } else if let Some('f') = c_option {
-                    Ok(DataType::Bool(false))
-                } else {
-                    Err("syntax error")
+                let label = self.labels.len();
+                self.labels.insert(address, label);
+                format!("#{}={}", label, self.structure(value))
+            }
+            _ => self.structure(value)
+        }
+    }
+
+    fn structure(&mut self, value: &DataType) -> String {
+        match *value {
+            DataType::Pair(ref p) => {
+                let mut items = vec![self.write(&p.car())];
+                let mut rest = p.cdr();
+                loop {
+                    rest = match rest {
+                        // a labelled tail has to be written after a dot
+                        DataType::Pair(ref pair) if !self.cyclic.contains(&address(&rest).unwrap()) => {
+                            items.push(self.write(&pair.car()));
+                            pair.cdr()
+                        }
+                        DataType::Null => break,
+                        ref tail => {
+                            items.push(".".to_string());
+                            items.push(self.write(tail));
+                            break;
+                        }
+                    };
                 }
-            } else if s.starts_with("\"") && s.ends_with("\"") {
-                Ok(DataType::Symbol((&s[1..s.len() - 1]).to_string()))
+                format!("({})", items.join(" "))
+            }
+            DataType::Vector(ref v) => {
+                let items = v.items().iter().map(|item| self.write(item)).collect::<Vec<_>>();
+                format!("#({})", items.join(" "))
+            }
+            ref other => (self.leaf)(other)
+        }
+    }
+}
+
+/// Writes `s` back in string literal syntax, minus the surrounding quotes.
+fn escape_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+/// Turns data computed by a procedural macro back into code, all of it at `span`.
+fn datatype2ast(value: &DataType, span: Span) -> Result<AST, SchemeError> {
+    match *value {
+        DataType::Null => Ok(AST::Children(vec![], span)),
+        DataType::Pair(ref p) => {
+            let mut children = vec![datatype2ast(&p.car(), span)?];
+            let mut rest = p.cdr();
+            loop {
+                rest = match rest {
+                    DataType::Pair(ref pair) => {
+                        children.push(datatype2ast(&pair.car(), span)?);
+                        pair.cdr()
+                    }
+                    DataType::Null => break,
+                    ref tail => {
+                        children.push(AST::Symbol(".".to_string(), span));
+                        children.push(datatype2ast(tail, span)?);
+                        break;
+                    }
+                };
+            }
+            Ok(AST::Children(children, span))
+        }
+        DataType::Symbol(ref s) => Ok(AST::Symbol(s.clone(), span)),
+        DataType::Bool(b) => Ok(AST::Symbol((if b { "#t" } else { "#f" }).to_string(), span)),
+        DataType::String(ref s) => Ok(AST::String(s.borrow().clone(), span)),
+        DataType::Char(c) => Ok(AST::Symbol(write_char(c), span)),
+        DataType::Vector(ref v) => {
+            let items = v.borrow().iter().map(|d| datatype2ast(d, span)).collect::<Result<Vec<_>, _>>()?;
+            Ok(AST::Vector(items, span))
+        }
+        DataType::Bytevector(ref b) => Ok(AST::Bytevector(b.items(), span)),
+        DataType::Number(ref n) => Ok(AST::Number(n.clone(), span)),
+        ref other => Err(SchemeError::wrong_type("macro expansion", "code", other))
+    }
+}
+
+fn ast2datatype(value: &AST) -> Result<DataType, SchemeError> {
+    match *value {
+        AST::Children(ref v, _) => match v.len() {
+            n if n >= 2 && is_dot(&v[n - 2]) => {
+                let items = v[..n - 2].iter().map(ast2datatype).collect::<Result<Vec<_>, _>>()?;
+                Ok(DataType::list_with_tail(items, ast2datatype(&v[n - 1])?))
+            }
+            _ => Ok(DataType::list(v.iter().map(ast2datatype).collect::<Result<Vec<_>, _>>()?))
+        },
+        AST::Symbol(ref s, _) => {
+            if s.starts_with('#') {
+                parse_hash(s)
             } else {
                 Ok(DataType::Symbol(s.clone()))
             }
         }
-        &AST::Integer(i) => Ok(DataType::Number(i as f64)),
-        &AST::Float(f) => Ok(DataType::Number(f))
+        AST::String(ref s, _) => Ok(DataType::string(s.clone())),
+        AST::Vector(ref v, _) => {
+            let items = v.iter().map(ast2datatype).collect::<Result<Vec<_>, _>>()?;
+            Ok(DataType::Vector(Vector::new(items)))
+        }
+        AST::Bytevector(ref b, _) => Ok(DataType::Bytevector(Bytevector::new(b.clone()))),
+        AST::Number(ref n, _) => Ok(DataType::Number(n.clone()))
     }
 }
@@ -0,0 +1,495 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{ast2datatype, call, check_arity, datatype2ast, Arity, DataType, Env, Function, Span, AST, SchemeError};
+
+/// Counts macro expansions, so identifiers renamed by different expansions
+/// never clash.
+static EXPANSIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// A macro: something that rewrites the forms it is used in before they are evaluated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Macro {
+    SyntaxRules(SyntaxRules),
+    /// Defined with `define-macro`: a procedure called with the operands of the
+    /// macro use, as data, and returning the expansion, with no hygiene.
+    Procedural(Box<DataType>),
+    /// Made by `er-macro-transformer`: a procedure called with the whole form,
+    /// a `rename` procedure and a `compare` procedure. Identifiers passed
+    /// through `rename` are treated like those a `syntax-rules` template introduces.
+    ExplicitRenaming(Box<DataType>)
+}
+
+/// A macro defined with `syntax-rules`.
+///
+/// Expansion is hygienic in that an identifier the template introduces in a
+/// binding position, such as the `tmp` of a `swap!` macro, is renamed, so it
+/// can neither capture nor be captured by the identifiers of the macro use.
+/// Identifiers the template uses freely are looked up where the macro is used.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxRules {
+    name: String,
+    ellipsis: String,
+    literals: Vec<String>,
+    rules: Vec<(AST, AST)>
+}
+
+/// What a pattern variable matched: a single form, or one binding per
+/// repetition of the ellipsis pattern it appears under.
+#[derive(Clone, Debug)]
+enum Binding {
+    One(AST),
+    Many(Vec<Binding>)
+}
+
+type Bindings = HashMap<String, Binding>;
+
+/// The aliases given to the identifiers one expansion introduces.
+struct Renames {
+    id: usize,
+    names: HashMap<String, String>
+}
+
+impl Renames {
+    fn new() -> Renames {
+        Renames { id: EXPANSIONS.fetch_add(1, Ordering::Relaxed), names: HashMap::new() }
+    }
+
+    fn alias(&mut self, name: &str) -> String {
+        let id = self.id;
+        self.names.entry(name.to_string()).or_insert_with(|| format!("{}#{}", name, id)).clone()
+    }
+
+    /// Maps each alias back to the identifier it stands for.
+    fn aliases(&self) -> HashMap<String, String> {
+        self.names.iter().map(|(name, alias)| (alias.clone(), name.clone())).collect()
+    }
+}
+
+/// The `rename` procedure handed to an explicit-renaming transformer.
+fn rename_procedure(renames: &Rc<RefCell<Renames>>) -> DataType {
+    let renames = renames.clone();
+    DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "rename", vec);
+        check_arity("rename", &vec, Arity::Exactly(1))?;
+        match vec[0] {
+            DataType::Symbol(ref name) => Ok(Some(DataType::Symbol(renames.borrow_mut().alias(name)))),
+            ref other => Err(SchemeError::wrong_type("rename", "symbol", other))
+        }
+    })))
+}
+
+/// The `compare` procedure handed to an explicit-renaming transformer, which
+/// tells whether two identifiers, renamed or not, stand for the same one.
+fn compare_procedure(renames: &Rc<RefCell<Renames>>) -> DataType {
+    let renames = renames.clone();
+    DataType::Proc(Function(Rc::new(move |vec: Vec<DataType>, _: Rc<RefCell<Env>>| {
+        debug!("Function - name: {:?} - Args: {:?}", "compare", vec);
+        check_arity("compare", &vec, Arity::Exactly(2))?;
+        let aliases = renames.borrow().aliases();
+        let original = |value: &DataType| match *value {
+            DataType::Symbol(ref s) => Some(aliases.get(s).unwrap_or(s).clone()),
+            _ => None
+        };
+        Ok(Some(DataType::Bool(original(&vec[0]).is_some() && original(&vec[0]) == original(&vec[1]))))
+    })))
+}
+
+impl Macro {
+    /// Rewrites the macro use `form`, which spans `span`. `env` is where
+    /// procedural transformers are called from.
+    pub fn expand(&self, form: &[AST], span: Span, env: Rc<RefCell<Env>>) -> Result<AST, SchemeError> {
+        let (expansion, aliases) = match *self {
+            Macro::SyntaxRules(ref rules) => return rules.expand(form, span),
+            Macro::Procedural(ref procedure) => {
+                let args = form[1..].iter().map(ast2datatype).collect::<Result<Vec<_>, _>>()?;
+                (call(procedure, args, env)?, HashMap::new())
+            }
+            Macro::ExplicitRenaming(ref procedure) => {
+                let renames = Rc::new(RefCell::new(Renames::new()));
+                let form = ast2datatype(&AST::Children(form.to_vec(), span))?;
+                let expansion = call(procedure, vec![form, rename_procedure(&renames), compare_procedure(&renames)], env)?;
+                let aliases = renames.borrow().aliases();
+                (expansion, aliases)
+            }
+        };
+        let name = match form[0] {
+            AST::Symbol(ref name, _) => name.as_str(),
+            _ => "macro"
+        };
+        let expansion = match expansion {
+            Some(expansion) => datatype2ast(&expansion, span)?,
+            None => return Err(SchemeError::bad_syntax(name, "transformer returned no expansion"))
+        };
+        debug!("expansion of {}: {:?}", name, expansion);
+        Ok(hygiene(expansion, &aliases))
+    }
+}
+
+impl SyntaxRules {
+    /// Builds the macro `name` from a `(syntax-rules ...)` form.
+    pub fn new(name: &str, spec: &AST) -> Result<SyntaxRules, SchemeError> {
+        let parts = match *spec {
+            AST::Children(ref parts, _) if is_symbol(parts.first(), "syntax-rules") => &parts[1..],
+            _ => return Err(SchemeError::bad_syntax(name, "expected a syntax-rules transformer"))
+        };
+        let (ellipsis, parts) = match parts.first() {
+            Some(AST::Symbol(ellipsis, _)) => (ellipsis.clone(), &parts[1..]),
+            _ => ("...".to_string(), parts)
+        };
+        let literals = match parts.first() {
+            Some(AST::Children(literals, _)) => literals.iter().map(|literal|
+                match *literal {
+                    AST::Symbol(ref s, _) => Ok(s.clone()),
+                    _ => Err(SchemeError::bad_syntax("syntax-rules", "literals must be symbols"))
+                }
+            ).collect::<Result<Vec<String>, _>>()?,
+            _ => return Err(SchemeError::bad_syntax("syntax-rules", "expected a list of literals"))
+        };
+        let rules = parts[1..].iter().map(|rule|
+            match *rule {
+                AST::Children(ref rule, _) if rule.len() == 2 && matches!(rule[0], AST::Children(..)) => {
+                    Ok((rule[0].clone(), rule[1].clone()))
+                }
+                _ => Err(SchemeError::bad_syntax("syntax-rules", "expected (pattern template)"))
+            }
+        ).collect::<Result<Vec<(AST, AST)>, _>>()?;
+
+        Ok(SyntaxRules { name: name.to_string(), ellipsis, literals, rules })
+    }
+
+    /// Rewrites the macro use `form` with the first rule whose pattern matches it.
+    fn expand(&self, form: &[AST], span: Span) -> Result<AST, SchemeError> {
+        for (pattern, template) in &self.rules {
+            let mut bindings = HashMap::new();
+            let pattern = match *pattern {
+                AST::Children(ref pattern, _) => pattern,
+                _ => unreachable!()
+            };
+            if !self.match_list(&pattern[1..], &form[1..], span, &mut bindings) {
+                continue;
+            }
+
+            let mut renames = Renames::new();
+            let expansion = self.instantiate(template, &bindings, &mut renames, false)?;
+            debug!("expansion of {}: {:?}", self.name, expansion);
+            return Ok(hygiene(expansion, &renames.aliases()));
+        }
+        Err(SchemeError::bad_syntax(&self.name, "no syntax rule matches"))
+    }
+
+    fn is_ellipsis(&self, ast: &AST) -> bool {
+        is_symbol(Some(ast), &self.ellipsis)
+    }
+
+    fn matches(&self, pattern: &AST, form: &AST, bindings: &mut Bindings) -> bool {
+        match *pattern {
+            AST::Symbol(ref name, _) if name == "_" => true,
+            AST::Symbol(ref name, _) if self.literals.contains(name) || name.starts_with('#') => {
+                is_symbol(Some(form), name)
+            }
+            AST::Symbol(ref name, _) => {
+                bindings.insert(name.clone(), Binding::One(form.clone()));
+                true
+            }
+            AST::Children(ref patterns, _) => match *form {
+                AST::Children(ref forms, span) => self.match_list(patterns, forms, span, bindings),
+                _ => false
+            },
+            AST::Vector(ref patterns, _) => match *form {
+                AST::Vector(ref forms, span) => self.match_list(patterns, forms, span, bindings),
+                _ => false
+            },
+            AST::Number(ref n, _) => matches!(*form, AST::Number(ref m, _) if m == n),
+            AST::String(ref s, _) => matches!(*form, AST::String(ref t, _) if t == s),
+            AST::Bytevector(ref b, _) => matches!(*form, AST::Bytevector(ref c, _) if c == b)
+        }
+    }
+
+    /// Matches the elements of a list pattern, which may contain one ellipsis
+    /// and end in a dotted tail, against the elements of a list form.
+    fn match_list(&self, patterns: &[AST], forms: &[AST], span: Span, bindings: &mut Bindings) -> bool {
+        let (patterns, tail) = split_tail(patterns);
+        let (forms, form_tail) = split_tail(forms);
+
+        let (before, repeated, after) = match patterns.iter().position(|p| self.is_ellipsis(p)) {
+            Some(i) if i > 0 => (&patterns[..i - 1], Some(&patterns[i - 1]), &patterns[i + 1..]),
+            Some(_) => return false,
+            None => (patterns, None, &patterns[patterns.len()..])
+        };
+        if forms.len() < before.len() + after.len() || (repeated.is_none() && tail.is_none() && forms.len() > before.len()) {
+            return false;
+        }
+        let count = match repeated {
+            Some(_) => forms.len() - before.len() - after.len(),
+            None => 0
+        };
+
+        let (head, rest) = forms.split_at(before.len());
+        if !before.iter().zip(head).all(|(p, f)| self.matches(p, f, bindings)) {
+            return false;
+        }
+        let (middle, rest) = rest.split_at(count);
+        if let Some(repeated) = repeated {
+            let mut matched = vec![];
+            for form in middle {
+                let mut inner = HashMap::new();
+                if !self.matches(repeated, form, &mut inner) {
+                    return false;
+                }
+                matched.push(inner);
+            }
+            let mut variables = vec![];
+            self.pattern_variables(repeated, &mut variables);
+            for variable in variables {
+                let values = matched.iter_mut().map(|m| m.remove(&variable).unwrap()).collect();
+                bindings.insert(variable, Binding::Many(values));
+            }
+        }
+        let (matched, rest) = rest.split_at(after.len());
+        if !after.iter().zip(matched).all(|(p, f)| self.matches(p, f, bindings)) {
+            return false;
+        }
+
+        match tail {
+            Some(tail) => {
+                let rest = match (rest.is_empty(), form_tail) {
+                    (true, Some(form_tail)) => form_tail.clone(),
+                    (_, form_tail) => {
+                        let mut items = rest.to_vec();
+                        if let Some(form_tail) = form_tail {
+                            items.push(AST::Symbol(".".to_string(), span));
+                            items.push(form_tail.clone());
+                        }
+                        AST::Children(items, span)
+                    }
+                };
+                self.matches(tail, &rest, bindings)
+            }
+            None => form_tail.is_none()
+        }
+    }
+
+    fn pattern_variables(&self, pattern: &AST, variables: &mut Vec<String>) {
+        match *pattern {
+            AST::Symbol(ref name, _) if name != "_" && name != "." && !name.starts_with('#') &&
+                *name != self.ellipsis && !self.literals.contains(name) => {
+                variables.push(name.clone());
+            }
+            AST::Children(ref patterns, _) | AST::Vector(ref patterns, _) => {
+                for pattern in patterns {
+                    self.pattern_variables(pattern, variables);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Fills in `template` with the forms the pattern variables matched.
+    /// Every other identifier is replaced by an alias recorded in `renames`.
+    fn instantiate(&self, template: &AST, bindings: &Bindings, renames: &mut Renames,
+                   escaped: bool) -> Result<AST, SchemeError> {
+        let (items, span) = match *template {
+            AST::Symbol(ref name, span) => {
+                return match bindings.get(name) {
+                    Some(Binding::One(form)) => Ok(form.clone()),
+                    Some(Binding::Many(_)) => {
+                        Err(SchemeError::bad_syntax(&self.name, &format!("{} must be followed by {}", name, self.ellipsis)))
+                    }
+                    None if name == "." || name.starts_with('#') => Ok(template.clone()),
+                    None => Ok(AST::Symbol(renames.alias(name), span))
+                };
+            }
+            AST::Children(ref items, span) => (items, span),
+            AST::Vector(ref items, span) => {
+                let list = AST::Children(items.clone(), span);
+                return match self.instantiate(&list, bindings, renames, escaped)? {
+                    AST::Children(items, span) => Ok(AST::Vector(items, span)),
+                    other => Ok(other)
+                };
+            }
+            _ => return Ok(template.clone())
+        };
+
+        if !escaped && items.len() == 2 && self.is_ellipsis(&items[0]) {
+            return self.instantiate(&items[1], bindings, renames, true);
+        }
+        let mut result = vec![];
+        let mut i = 0;
+        while i < items.len() {
+            let mut depth = 0;
+            while !escaped && items.get(i + depth + 1).is_some_and(|t| self.is_ellipsis(t)) {
+                depth += 1;
+            }
+            if depth > 0 {
+                self.instantiate_repeated(&items[i], bindings, renames, depth, &mut result)?;
+            } else if is_symbol(Some(&items[i]), ".") && i + 2 == items.len() {
+                match self.instantiate(&items[i + 1], bindings, renames, escaped)? {
+                    AST::Children(tail, _) => result.extend(tail),
+                    tail => {
+                        result.push(items[i].clone());
+                        result.push(tail);
+                    }
+                }
+                break;
+            } else {
+                result.push(self.instantiate(&items[i], bindings, renames, escaped)?);
+            }
+            i += depth + 1;
+        }
+        Ok(AST::Children(result, span))
+    }
+
+    /// Instantiates `template`, followed by `depth` ellipses, once for each
+    /// repetition of the pattern variables it contains.
+    fn instantiate_repeated(&self, template: &AST, bindings: &Bindings, renames: &mut Renames,
+                            depth: usize, result: &mut Vec<AST>) -> Result<(), SchemeError> {
+        let mut variables = vec![];
+        self.pattern_variables(template, &mut variables);
+        let repeated = variables.into_iter().filter_map(|variable|
+            match bindings.get(&variable) {
+                Some(Binding::Many(values)) => Some((variable, values)),
+                _ => None
+            }
+        ).collect::<Vec<_>>();
+        let count = match repeated.first() {
+            Some(&(_, values)) => values.len(),
+            None => return Err(SchemeError::bad_syntax(&self.name, "no pattern variable to repeat before ellipsis"))
+        };
+        if repeated.iter().any(|&(_, values)| values.len() != count) {
+            return Err(SchemeError::bad_syntax(&self.name, "pattern variables repeated by one ellipsis matched different lengths"));
+        }
+
+        for i in 0..count {
+            let mut inner = bindings.clone();
+            for &(ref variable, values) in &repeated {
+                inner.insert(variable.clone(), values[i].clone());
+            }
+            if depth > 1 {
+                self.instantiate_repeated(template, &inner, renames, depth - 1, result)?;
+            } else {
+                result.push(self.instantiate(template, &inner, renames, false)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_symbol(ast: Option<&AST>, name: &str) -> bool {
+    matches!(ast, Some(AST::Symbol(s, _)) if s == name)
+}
+
+/// Splits a dotted `(a b . c)` list into its elements and its tail.
+fn split_tail(items: &[AST]) -> (&[AST], Option<&AST>) {
+    match items.len() {
+        n if n >= 2 && is_symbol(items.get(n - 2), ".") => (&items[..n - 2], Some(&items[n - 1])),
+        _ => (items, None)
+    }
+}
+
+/// The keyword `ast` names, seeing through the aliases of one expansion.
+fn keyword<'a>(ast: &'a AST, aliases: &'a HashMap<String, String>) -> Option<&'a str> {
+    match *ast {
+        AST::Symbol(ref s, _) => Some(aliases.get(s).unwrap_or(s)),
+        _ => None
+    }
+}
+
+/// Collects the aliases `expansion` binds with `lambda`, `define` and the other
+/// binding forms into `bound`.
+fn binders(expansion: &AST, aliases: &HashMap<String, String>, bound: &mut HashSet<String>) {
+    let items = match *expansion {
+        AST::Children(ref items, _) => items,
+        _ => return
+    };
+    let mut formals = vec![];
+    match items.first().and_then(|head| keyword(head, aliases)) {
+        Some("quote") => return,
+        Some("lambda") | Some("define") | Some("define-values") | Some("define-syntax") => {
+            formals.extend(items.get(1));
+        }
+        Some("let") | Some("let*") | Some("letrec") | Some("letrec*") | Some("let-values") |
+        Some("let*-values") | Some("let-syntax") | Some("letrec-syntax") | Some("do") => {
+            let bindings = match items.get(1) {
+                Some(name @ AST::Symbol(..)) => {
+                    formals.push(name);
+                    items.get(2)
+                }
+                bindings => bindings
+            };
+            if let Some(AST::Children(bindings, _)) = bindings {
+                formals.extend(bindings.iter().filter_map(|binding|
+                    match *binding {
+                        AST::Children(ref binding, _) => binding.first(),
+                        _ => None
+                    }
+                ));
+            }
+        }
+        Some("case-lambda") => {
+            formals.extend(items[1..].iter().filter_map(|clause|
+                match *clause {
+                    AST::Children(ref clause, _) => clause.first(),
+                    _ => None
+                }
+            ));
+        }
+        _ => {}
+    }
+    for formal in formals {
+        symbols(formal, &mut |s| if aliases.contains_key(s) {
+            bound.insert(s.to_string());
+        });
+    }
+    for item in items {
+        binders(item, aliases, bound);
+    }
+}
+
+fn symbols<F: FnMut(&str)>(ast: &AST, f: &mut F) {
+    match *ast {
+        AST::Symbol(ref s, _) => f(s),
+        AST::Children(ref items, _) => {
+            for item in items {
+                symbols(item, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Renames the aliases `expansion` binds, and turns the others back into the
+/// identifiers they stand for.
+fn hygiene(expansion: AST, aliases: &HashMap<String, String>) -> AST {
+    let mut bound = HashSet::new();
+    binders(&expansion, aliases, &mut bound);
+    rename(expansion, aliases, &bound)
+}
+
+/// Turns the aliases in `expansion` back into the identifiers they stand for,
+/// except for those in `bound`, which keep their fresh names outside of quoted data.
+fn rename(expansion: AST, aliases: &HashMap<String, String>, bound: &HashSet<String>) -> AST {
+    match expansion {
+        AST::Symbol(s, span) => match aliases.get(&s) {
+            Some(name) if !bound.contains(&s) => AST::Symbol(name.clone(), span),
+            _ => AST::Symbol(s, span)
+        },
+        AST::Children(items, span) => {
+            let bound = match items.first().and_then(|head| keyword(head, aliases)) {
+                Some("quote") => None,
+                _ => Some(bound)
+            };
+            let empty = HashSet::new();
+            let bound = bound.unwrap_or(&empty);
+            AST::Children(items.into_iter().map(|item| rename(item, aliases, bound)).collect(), span)
+        }
+        // a vector literal is quoted data
+        AST::Vector(items, span) => {
+            let empty = HashSet::new();
+            AST::Vector(items.into_iter().map(|item| rename(item, aliases, &empty)).collect(), span)
+        }
+        other => other
+    }
+}
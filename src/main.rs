@@ -24,9 +24,9 @@ fn main() {
     // 1) https://stackoverflow.com/questions/48034119/rust-matching-a-optionstring
     // 2) https://stackoverflow.com/questions/31233938/converting-from-optionstring-to-optionstr
     // make vector <T> to option<T> so we can then call #as_ref for all elements
-    let args_options = env::args().map(|x| Some(x)).collect::<Vec<Option<String>>>();
-    let args_ref = args_options.iter().map(|x| x.as_ref().map(|s| s.as_str())) // convert option<T> to option<&T>
-        .flat_map(|x| x) // convert option<T> to T
+    let args_options = env::args().map(Some).collect::<Vec<Option<String>>>();
+    let args_ref = args_options.iter()
+        .filter_map(|x| x.as_ref().map(|s| s.as_str())) // convert option<T> to option<&T>, then to T
         .collect::<Vec<&str>>();
 
     debug!("args_ref: {:?}", args_ref);
@@ -43,7 +43,7 @@ fn main() {
                 let mut f = File::open(path).expect("Error: file not found");
                 let mut code = String::new();
                 f.read_to_string(&mut code).expect("Error: cannot read the file.");
-                execute(code);
+                execute(path_input, code);
             } else {
                 println!("Error: file not found.");
             }
@@ -52,7 +52,7 @@ fn main() {
     }
 }
 
-fn execute(input: String) {
+fn execute(path: &str, input: String) {
     io::stdout().flush().expect("cannot flush screen");
 
     let local = Box::new(RefCell::new(scheme_rs::setup()));
@@ -66,7 +66,13 @@ fn execute(input: String) {
     match parse(input.as_str()).and_then(|ast| eval(Some(ast.result), rc_env.clone())) {
         Ok(Some(d)) => println!("{:?}", d),
         Ok(None) => {}
-        Err(e) => println!("error: {}", e)
+        Err(e) => {
+            // located errors already start with "line:column"
+            match e.span() {
+                Some(_) => println!("{}:{}", path, e),
+                None => println!("{}: error: {}", path, e)
+            }
+        }
     }
     debug!("ENV: {:?}", &rc_env);
 }
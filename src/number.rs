@@ -0,0 +1,544 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+
+/// A Scheme number. Exact integers are kept as fixnums while they fit in an
+/// `i64` and are promoted to bignums when they do not; exact non-integers are
+/// rationals. Complex numbers are always inexact. Results are always normalised
+/// to the simplest representation, so e.g. `Big` never holds a value that would
+/// fit in `Integer` and `Complex` never has a zero imaginary part.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    Big(BigInt),
+    Rational(BigRational),
+    Real(f64),
+    Complex(Complex64)
+}
+
+/// Two numbers converted to a common representation.
+enum Coerced {
+    Integer(i64, i64),
+    Big(BigInt, BigInt),
+    Rational(BigRational, BigRational),
+    Real(f64, f64),
+    Complex(Complex64, Complex64)
+}
+
+impl Number {
+    fn from_big(i: BigInt) -> Number {
+        match i.to_i64() {
+            Some(n) => Number::Integer(n),
+            None => Number::Big(i)
+        }
+    }
+
+    fn from_rational(r: BigRational) -> Number {
+        if r.is_integer() {
+            Number::from_big(r.to_integer())
+        } else {
+            Number::Rational(r)
+        }
+    }
+
+    fn from_complex(c: Complex64) -> Number {
+        if c.im == 0.0 {
+            Number::Real(c.re)
+        } else {
+            Number::Complex(c)
+        }
+    }
+
+    /// The number `re + im·i`, which is `re` itself if `im` is an exact zero.
+    pub fn rectangular(re: &Number, im: &Number) -> Number {
+        if im.is_exact() && im.is_zero() {
+            re.clone()
+        } else {
+            Number::from_complex(Complex64::new(re.to_f64(), im.to_f64()))
+        }
+    }
+
+    /// The number with the given magnitude and angle, which is `magnitude`
+    /// itself if `angle` is an exact zero.
+    pub fn polar(magnitude: &Number, angle: &Number) -> Number {
+        if angle.is_exact() && angle.is_zero() {
+            magnitude.clone()
+        } else {
+            Number::from_complex(Complex64::from_polar(magnitude.to_f64(), angle.to_f64()))
+        }
+    }
+
+    fn to_big(&self) -> Option<BigInt> {
+        match *self {
+            Number::Integer(i) => Some(BigInt::from(i)),
+            Number::Big(ref i) => Some(i.clone()),
+            _ => None
+        }
+    }
+
+    /// The value of an exact integer that fits in an `i128`.
+    pub fn to_i128(&self) -> Option<i128> {
+        match *self {
+            Number::Integer(i) => Some(i128::from(i)),
+            Number::Big(ref i) => i.to_i128(),
+            _ => None
+        }
+    }
+
+    fn to_rational(&self) -> Option<BigRational> {
+        match *self {
+            Number::Integer(_) | Number::Big(_) => self.to_big().map(BigRational::from_integer),
+            Number::Rational(ref r) => Some(r.clone()),
+            Number::Real(_) | Number::Complex(_) => None
+        }
+    }
+
+    fn to_complex(&self) -> Complex64 {
+        match *self {
+            Number::Complex(c) => c,
+            _ => Complex64::new(self.to_f64(), 0.0)
+        }
+    }
+
+    fn coerce(&self, other: &Number) -> Coerced {
+        match (self, other) {
+            (&Number::Integer(a), &Number::Integer(b)) => Coerced::Integer(a, b),
+            (&Number::Complex(_), _) | (_, &Number::Complex(_)) => Coerced::Complex(self.to_complex(), other.to_complex()),
+            (&Number::Real(_), _) | (_, &Number::Real(_)) => Coerced::Real(self.to_f64(), other.to_f64()),
+            (&Number::Rational(_), _) | (_, &Number::Rational(_)) => {
+                Coerced::Rational(self.to_rational().unwrap(), other.to_rational().unwrap())
+            }
+            _ => Coerced::Big(self.to_big().unwrap(), other.to_big().unwrap())
+        }
+    }
+
+    /// Whether `self` and `other` are the same in the sense of `eqv?`: unlike
+    /// `==`, inexact numbers must have the same bits, so `0.0` and `-0.0` differ.
+    pub fn eqv(&self, other: &Number) -> bool {
+        match (self, other) {
+            (Number::Real(a), Number::Real(b)) => a.to_bits() == b.to_bits(),
+            (Number::Complex(a), Number::Complex(b)) => a.re.to_bits() == b.re.to_bits() && a.im.to_bits() == b.im.to_bits(),
+            _ => self == other
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(*self, Number::Real(_) | Number::Complex(_))
+    }
+
+    pub fn is_real(&self) -> bool {
+        !matches!(*self, Number::Complex(_))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match *self {
+            Number::Integer(_) | Number::Big(_) => true,
+            Number::Rational(_) | Number::Complex(_) => false,
+            Number::Real(f) => f.is_finite() && f.fract() == 0.0
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            Number::Integer(i) => i == 0,
+            Number::Real(f) => f == 0.0,
+            // normalised bignums, rationals and complex numbers are never zero
+            _ => false
+        }
+    }
+
+    /// The nearest `f64`, which is NaN for non-real numbers.
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            Number::Integer(i) => i as f64,
+            Number::Big(ref i) => i.to_f64().unwrap_or(f64::NAN),
+            Number::Rational(ref r) => r.to_f64().unwrap_or(f64::NAN),
+            Number::Real(f) => f,
+            Number::Complex(_) => f64::NAN
+        }
+    }
+
+    /// The exact number equal to `self`, or `None` for infinities, NaNs and
+    /// complex numbers.
+    pub fn to_exact(&self) -> Option<Number> {
+        match *self {
+            Number::Real(f) => BigRational::from_f64(f).map(Number::from_rational),
+            Number::Complex(_) => None,
+            _ => Some(self.clone())
+        }
+    }
+
+    pub fn to_inexact(&self) -> Number {
+        match *self {
+            Number::Complex(_) => self.clone(),
+            _ => Number::Real(self.to_f64())
+        }
+    }
+
+    pub fn add(&self, other: &Number) -> Number {
+        match self.coerce(other) {
+            Coerced::Integer(a, b) => match a.checked_add(b) {
+                Some(n) => Number::Integer(n),
+                None => Number::from_big(BigInt::from(a) + BigInt::from(b))
+            },
+            Coerced::Big(a, b) => Number::from_big(a + b),
+            Coerced::Rational(a, b) => Number::from_rational(a + b),
+            Coerced::Real(a, b) => Number::Real(a + b),
+            Coerced::Complex(a, b) => Number::from_complex(a + b)
+        }
+    }
+
+    pub fn sub(&self, other: &Number) -> Number {
+        match self.coerce(other) {
+            Coerced::Integer(a, b) => match a.checked_sub(b) {
+                Some(n) => Number::Integer(n),
+                None => Number::from_big(BigInt::from(a) - BigInt::from(b))
+            },
+            Coerced::Big(a, b) => Number::from_big(a - b),
+            Coerced::Rational(a, b) => Number::from_rational(a - b),
+            Coerced::Real(a, b) => Number::Real(a - b),
+            Coerced::Complex(a, b) => Number::from_complex(a - b)
+        }
+    }
+
+    pub fn mul(&self, other: &Number) -> Number {
+        match self.coerce(other) {
+            Coerced::Integer(a, b) => match a.checked_mul(b) {
+                Some(n) => Number::Integer(n),
+                None => Number::from_big(BigInt::from(a) * BigInt::from(b))
+            },
+            Coerced::Big(a, b) => Number::from_big(a * b),
+            Coerced::Rational(a, b) => Number::from_rational(a * b),
+            Coerced::Real(a, b) => Number::Real(a * b),
+            Coerced::Complex(a, b) => Number::from_complex(a * b)
+        }
+    }
+
+    /// `self / other`, or `None` when dividing an exact number by exact zero.
+    pub fn div(&self, other: &Number) -> Option<Number> {
+        match self.coerce(other) {
+            Coerced::Real(a, b) => Some(Number::Real(a / b)),
+            Coerced::Complex(a, b) => Some(Number::from_complex(a / b)),
+            _ if other.is_zero() => None,
+            Coerced::Integer(a, b) => {
+                Some(Number::from_rational(BigRational::new(BigInt::from(a), BigInt::from(b))))
+            }
+            Coerced::Big(a, b) => Some(Number::from_rational(BigRational::new(a, b))),
+            Coerced::Rational(a, b) => Some(Number::from_rational(a / b))
+        }
+    }
+
+    pub fn neg(&self) -> Number {
+        match *self {
+            // 0 - 0.0 would lose the sign of zero
+            Number::Real(f) => Number::Real(-f),
+            Number::Complex(c) => Number::Complex(-c),
+            _ => Number::Integer(0).sub(self)
+        }
+    }
+
+    pub fn abs(&self) -> Number {
+        match *self {
+            Number::Integer(i) if i < 0 => self.neg(),
+            Number::Big(ref i) => Number::from_big(i.abs()),
+            Number::Rational(ref r) => Number::from_rational(r.abs()),
+            Number::Real(f) => Number::Real(f.abs()),
+            Number::Complex(c) => Number::Real(c.norm()),
+            _ => self.clone()
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        self.compare(&Number::Integer(0)) == Some(Ordering::Less)
+    }
+
+    /// The principal square root, which is exact for exact perfect squares.
+    pub fn sqrt(&self) -> Number {
+        match *self {
+            Number::Complex(c) => Number::from_complex(c.sqrt()),
+            _ if self.is_negative() => Number::rectangular(&Number::Integer(0), &self.neg().sqrt()),
+            Number::Integer(_) | Number::Big(_) => match exact_sqrt(&self.to_big().unwrap()) {
+                Some(root) => Number::from_big(root),
+                None => Number::Real(self.to_f64().sqrt())
+            },
+            Number::Rational(ref r) => match (exact_sqrt(r.numer()), exact_sqrt(r.denom())) {
+                (Some(n), Some(d)) => Number::from_rational(BigRational::new(n, d)),
+                _ => Number::Real(self.to_f64().sqrt())
+            },
+            Number::Real(f) => Number::Real(f.sqrt())
+        }
+    }
+
+    pub fn exp(&self) -> Number {
+        match *self {
+            Number::Complex(c) => Number::from_complex(c.exp()),
+            _ if self.is_exact() && self.is_zero() => Number::Integer(1),
+            _ => Number::Real(self.to_f64().exp())
+        }
+    }
+
+    /// The natural logarithm, which is complex for negative numbers.
+    pub fn log(&self) -> Number {
+        match *self {
+            Number::Complex(c) => Number::from_complex(c.ln()),
+            Number::Integer(1) => Number::Integer(0),
+            _ if self.is_negative() => Number::from_complex(self.to_complex().ln()),
+            _ => Number::Real(self.to_f64().ln())
+        }
+    }
+
+    pub fn real_part(&self) -> Number {
+        match *self {
+            Number::Complex(c) => Number::Real(c.re),
+            _ => self.clone()
+        }
+    }
+
+    pub fn imag_part(&self) -> Number {
+        match *self {
+            Number::Complex(c) => Number::Real(c.im),
+            _ => Number::Integer(0)
+        }
+    }
+
+    pub fn magnitude(&self) -> Number {
+        self.abs()
+    }
+
+    pub fn angle(&self) -> Number {
+        match *self {
+            Number::Complex(c) => Number::Real(c.arg()),
+            _ if self.is_negative() => Number::Real(::std::f64::consts::PI),
+            _ if self.is_exact() => Number::Integer(0),
+            _ => Number::Real(0.0)
+        }
+    }
+
+    /// Numeric comparison; `None` when either side is a NaN, or when the numbers
+    /// differ and are not both real.
+    pub fn compare(&self, other: &Number) -> Option<Ordering> {
+        match self.coerce(other) {
+            Coerced::Integer(a, b) => Some(a.cmp(&b)),
+            Coerced::Big(a, b) => Some(a.cmp(&b)),
+            Coerced::Rational(a, b) => Some(a.cmp(&b)),
+            Coerced::Real(a, b) => a.partial_cmp(&b),
+            Coerced::Complex(a, b) if a == b => Some(Ordering::Equal),
+            Coerced::Complex(..) => None
+        }
+    }
+
+    /// Parses a numeric literal following the R7RS grammar: an optional radix
+    /// (`#x`, `#d`, `#o`, `#b`) and exactness (`#e`, `#i`) prefix in either order,
+    /// followed by a real number or a complex number written as `a+bi`, `+bi` or
+    /// `magnitude@angle`. Returns `None` if `text` is not a number.
+    pub fn parse(text: &str) -> Option<Number> {
+        Number::parse_with_radix(text, 10)
+    }
+
+    /// Like `parse`, but reads digits in `default_radix` unless `text` has a
+    /// radix prefix of its own.
+    pub fn parse_with_radix(text: &str, default_radix: u32) -> Option<Number> {
+        let mut radix = None;
+        let mut exactness = None;
+        let mut body = text;
+        while body.starts_with('#') {
+            let prefix = body.get(1..2)?.to_ascii_lowercase();
+            match prefix.as_str() {
+                "x" | "d" | "o" | "b" if radix.is_none() => {
+                    radix = Some(match prefix.as_str() { "x" => 16, "d" => 10, "o" => 8, _ => 2 });
+                }
+                "e" | "i" if exactness.is_none() => exactness = Some(prefix == "e"),
+                _ => return None
+            }
+            body = &body[2..];
+        }
+
+        let number = parse_complex(body, radix.unwrap_or(default_radix), exactness)?;
+        match exactness {
+            Some(true) => number.to_exact(),
+            Some(false) => Some(number.to_inexact()),
+            None => Some(number)
+        }
+    }
+
+    /// Writes the number in `radix`. Only exact numbers can be written in a
+    /// radix other than 10; `None` is returned for inexact ones.
+    pub fn to_string_radix(&self, radix: u32) -> Option<String> {
+        match *self {
+            _ if radix == 10 => Some(self.to_string()),
+            Number::Integer(i) => Some(BigInt::from(i).to_str_radix(radix)),
+            Number::Big(ref i) => Some(i.to_str_radix(radix)),
+            Number::Rational(ref r) => Some(format!("{}/{}", r.numer().to_str_radix(radix), r.denom().to_str_radix(radix))),
+            Number::Real(_) | Number::Complex(_) => None
+        }
+    }
+}
+
+fn exact_sqrt(i: &BigInt) -> Option<BigInt> {
+    let root = i.sqrt();
+    if &root * &root == *i {
+        Some(root)
+    } else {
+        None
+    }
+}
+
+/// `exact` is the exactness prefix, if any: `Some(true)` for `#e`.
+fn parse_complex(text: &str, radix: u32, exact: Option<bool>) -> Option<Number> {
+    if let Some(at) = text.find('@') {
+        let magnitude = parse_real(&text[..at], radix, exact)?;
+        let angle = parse_real(&text[at + 1..], radix, exact)?;
+        return Some(Number::polar(&magnitude, &angle));
+    }
+
+    let body = match text.strip_suffix(['i', 'I']) {
+        Some(body) => body,
+        None => return parse_real(text, radix, exact)
+    };
+    // the imaginary part starts at the last sign that is not part of an exponent
+    let split = body.char_indices().rev()
+        .find(|&(i, c)| (c == '+' || c == '-') && !(radix == 10 && body[..i].ends_with(['e', 'E'])))
+        .map(|(i, _)| i)?;
+    let real = match split {
+        0 => Number::Integer(0),
+        _ => parse_real(&body[..split], radix, exact)?
+    };
+    let imag = match &body[split..] {
+        "+" => Number::Integer(1),
+        "-" => Number::Integer(-1),
+        imag => parse_real(imag, radix, exact)?
+    };
+    Some(Number::rectangular(&real, &imag))
+}
+
+/// Parses an optionally signed integer, `n/d` rational or decimal, or one of
+/// `+inf.0`, `-inf.0`, `+nan.0` and `-nan.0`.
+fn parse_real(text: &str, radix: u32, exact: Option<bool>) -> Option<Number> {
+    match text.to_ascii_lowercase().as_str() {
+        "+inf.0" => return Some(Number::Real(f64::INFINITY)),
+        "-inf.0" => return Some(Number::Real(f64::NEG_INFINITY)),
+        "+nan.0" | "-nan.0" => return Some(Number::Real(f64::NAN)),
+        _ => {}
+    }
+
+    let (negative, unsigned) = match text.as_bytes().first() {
+        Some(b'+') => (false, &text[1..]),
+        Some(b'-') => (true, &text[1..]),
+        _ => (false, text)
+    };
+    let number = parse_ureal(unsigned, radix, exact)?;
+    Some(if negative { number.neg() } else { number })
+}
+
+fn parse_ureal(text: &str, radix: u32, exact: Option<bool>) -> Option<Number> {
+    if let Some(slash) = text.find('/') {
+        let numerator = parse_uinteger(&text[..slash], radix)?;
+        let denominator = parse_uinteger(&text[slash + 1..], radix)?;
+        if denominator.is_zero() {
+            return None;
+        }
+        return Some(Number::from_rational(BigRational::new(numerator, denominator)));
+    }
+    if let Some(i) = parse_uinteger(text, radix) {
+        return Some(Number::from_big(i));
+    }
+    if radix == 10 {
+        return parse_decimal(text, exact == Some(true));
+    }
+    None
+}
+
+fn parse_uinteger(text: &str, radix: u32) -> Option<BigInt> {
+    if text.is_empty() || !text.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    BigInt::parse_bytes(text.as_bytes(), radix)
+}
+
+/// The largest power of ten an exact decimal such as `#e1e400` may be scaled
+/// by. Bigger exponents are rejected rather than computing huge numbers.
+const MAX_EXACT_SCALE: u64 = 10_000;
+
+/// Parses `digits[.digits][e[sign]digits]`, exactly if `exact` is set.
+fn parse_decimal(text: &str, exact: bool) -> Option<Number> {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(e) => (&text[..e], &text[e + 1..]),
+        None => (text, "0")
+    };
+    let (whole, fraction) = match mantissa.find('.') {
+        Some(dot) => (&mantissa[..dot], &mantissa[dot + 1..]),
+        None => (mantissa, "")
+    };
+    let exponent_digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+    let valid = !(whole.is_empty() && fraction.is_empty()) &&
+        whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) &&
+        !exponent_digits.is_empty() && exponent_digits.chars().all(|c| c.is_ascii_digit());
+    if !valid {
+        return None;
+    }
+
+    if !exact {
+        return text.parse::<f64>().ok().map(Number::Real);
+    }
+    let digits: BigInt = format!("0{}{}", whole, fraction).parse().ok()?;
+    let scale = exponent.parse::<i64>().ok()? - fraction.len() as i64;
+    if scale.unsigned_abs() > MAX_EXACT_SCALE {
+        return None;
+    }
+    let power = BigInt::from(10).pow(scale.unsigned_abs() as u32);
+    Some(Number::from_rational(if scale < 0 {
+        BigRational::new(digits, power)
+    } else {
+        BigRational::from_integer(digits * power)
+    }))
+}
+
+impl From<i64> for Number {
+    fn from(i: i64) -> Number {
+        Number::Integer(i)
+    }
+}
+
+impl From<i128> for Number {
+    fn from(i: i128) -> Number {
+        Number::from_big(BigInt::from(i))
+    }
+}
+
+impl From<f64> for Number {
+    fn from(f: f64) -> Number {
+        Number::Real(f)
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Number::Integer(i) => write!(f, "{}", i),
+            Number::Big(ref i) => write!(f, "{}", i),
+            Number::Rational(ref r) => write!(f, "{}/{}", r.numer(), r.denom()),
+            Number::Real(x) => write!(f, "{}", real2str(x)),
+            Number::Complex(c) => {
+                let imag = real2str(c.im);
+                let sign = if imag.starts_with(['+', '-']) { "" } else { "+" };
+                write!(f, "{}{}{}i", real2str(c.re), sign, imag)
+            }
+        }
+    }
+}
+
+fn real2str(x: f64) -> String {
+    if x.is_nan() {
+        "+nan.0".to_string()
+    } else if x.is_infinite() {
+        format!("{}inf.0", if x > 0.0 { "+" } else { "-" })
+    } else {
+        // unlike Display, Debug keeps a ".0" on integral values and switches to
+        // exponent notation for very large or small magnitudes
+        format!("{:?}", x)
+    }
+}
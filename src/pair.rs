@@ -0,0 +1,70 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+use super::{equal, write_shared, DataType};
+
+/// A mutable cons cell, shared by its clones like a `Shared` object, so lists
+/// can share their tails.
+#[derive(Clone)]
+pub struct Pair(pub(crate) Rc<RefCell<(DataType, DataType)>>);
+
+impl Pair {
+    pub fn new(car: DataType, cdr: DataType) -> Pair {
+        Pair(Rc::new(RefCell::new((car, cdr))))
+    }
+
+    pub fn car(&self) -> DataType {
+        self.0.borrow().0.clone()
+    }
+
+    pub fn cdr(&self) -> DataType {
+        self.0.borrow().1.clone()
+    }
+
+    pub fn set_car(&self, value: DataType) {
+        self.0.borrow_mut().0 = value;
+    }
+
+    pub fn set_cdr(&self, value: DataType) {
+        self.0.borrow_mut().1 = value;
+    }
+
+    /// Whether `self` and `other` are the same cell.
+    pub fn ptr_eq(&self, other: &Pair) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Compares the cells' contents as `equal?` does.
+impl PartialEq for Pair {
+    fn eq(&self, other: &Pair) -> bool {
+        equal(&DataType::Pair(self.clone()), &DataType::Pair(other.clone()))
+    }
+}
+
+/// Prints the list starting at the cell in list notation, e.g.
+/// `(Number(Integer(1)) . Bool(true))`.
+impl fmt::Debug for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", write_shared(&DataType::Pair(self.clone()), &|leaf| format!("{:?}", leaf)))
+    }
+}
+
+/// Unlinks the cells of a list no one else refers to one at a time, instead
+/// of letting each cdr drop the next recursively.
+impl Drop for Pair {
+    fn drop(&mut self) {
+        let mut next = match Rc::get_mut(&mut self.0) {
+            Some(cell) => mem::replace(&mut cell.get_mut().1, DataType::Null),
+            None => return
+        };
+        while let DataType::Pair(mut pair) = next {
+            next = match Rc::get_mut(&mut pair.0) {
+                Some(cell) => mem::replace(&mut cell.get_mut().1, DataType::Null),
+                None => break
+            };
+        }
+    }
+}
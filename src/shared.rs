@@ -0,0 +1,50 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::Rc;
+
+/// A mutable object that clones refer to rather than copy, so a change made
+/// through one of them, such as by `vector-set!`, is seen through all of them.
+/// Strings, vectors and bytevectors are each a `Shared` of their contents.
+#[derive(Clone)]
+pub struct Shared<T>(pub(crate) Rc<RefCell<T>>);
+
+impl<T> Shared<T> {
+    pub fn new(value: T) -> Shared<T> {
+        Shared(Rc::new(RefCell::new(value)))
+    }
+
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.0.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.0.borrow_mut()
+    }
+
+    /// Whether `self` and `other` are the same object, as `eqv?` sees it.
+    pub fn ptr_eq(&self, other: &Shared<T>) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T: Clone> Shared<Vec<T>> {
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    /// A copy of the elements.
+    pub fn items(&self) -> Vec<T> {
+        self.0.borrow().clone()
+    }
+
+    /// Overwrites the elements from index `at` on with those of `from` from
+    /// index `start` up to `end`, which must fit.
+    pub fn copy_from(&self, at: usize, from: &Shared<Vec<T>>, start: usize, end: usize) {
+        // copied out first, as `from` may be `self`
+        let items = from.borrow()[start..end].to_vec();
+        self.0.borrow_mut()[at..at + items.len()].clone_from_slice(&items);
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+use super::Shared;
+
+/// A mutable string. Indices count characters, not bytes.
+pub type SchemeString = Shared<String>;
+
+impl SchemeString {
+    /// The number of characters.
+    pub fn len(&self) -> usize {
+        self.0.borrow().chars().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    /// The character at index `k`, if there is one.
+    pub fn get(&self, k: usize) -> Option<char> {
+        self.0.borrow().chars().nth(k)
+    }
+
+    /// Replaces the character at index `k` with `c`, or returns `false` if
+    /// the string is too short.
+    pub fn set(&self, k: usize, c: char) -> bool {
+        let mut s = self.0.borrow_mut();
+        let (start, old) = match s.char_indices().nth(k) {
+            Some(found) => found,
+            None => return false
+        };
+        s.replace_range(start..start + old.len_utf8(), c.encode_utf8(&mut [0; 4]));
+        true
+    }
+
+    /// Replaces the characters from index `start` up to `end` with `c`.
+    pub fn fill(&self, c: char, start: usize, end: usize) {
+        let mut s = self.0.borrow_mut();
+        let filled = s.chars().enumerate()
+            .map(|(i, old)| if i >= start && i < end { c } else { old })
+            .collect();
+        *s = filled;
+    }
+
+}
+
+impl PartialEq for SchemeString {
+    fn eq(&self, other: &SchemeString) -> bool {
+        *self.0.borrow() == *other.0.borrow()
+    }
+}
+
+/// Writes the characters themselves, as `display` does.
+impl fmt::Display for SchemeString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", *self.0.borrow())
+    }
+}
+
+impl fmt::Debug for SchemeString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", *self.0.borrow())
+    }
+}
@@ -0,0 +1,18 @@
+use std::fmt;
+
+use super::{equal, write_shared, DataType, Shared};
+
+/// A mutable vector.
+pub type Vector = Shared<Vec<DataType>>;
+
+impl PartialEq for Vector {
+    fn eq(&self, other: &Vector) -> bool {
+        equal(&DataType::Vector(self.clone()), &DataType::Vector(other.clone()))
+    }
+}
+
+impl fmt::Debug for Vector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", write_shared(&DataType::Vector(self.clone()), &|leaf| format!("{:?}", leaf)))
+    }
+}